@@ -1,5 +1,5 @@
 pub mod server;
-use std::{ collections::HashMap, fs };
+use std::{ collections::HashMap, fs, path::Path };
 
 use regex::Regex;
 pub use server::*;
@@ -22,6 +22,9 @@ impl Config {
                 access_log_format: String::new(),
                 timeout: 0,
                 size_limit: 0,
+                static_cache: StaticCacheConfig::default(),
+                upstreams: HashMap::new(),
+                session_store: SessionStoreConfig::default(),
                 servers: HashMap::new(),
             },
         }
@@ -40,9 +43,87 @@ pub struct HttpConfig {
     pub access_log_format: String,
     pub timeout: u64,
     pub size_limit: usize,
+    #[serde(default)]
+    pub static_cache: StaticCacheConfig,
+    #[serde(default)]
+    pub upstreams: HashMap<String, UpstreamGroup>, // groupes de serveurs des locations proxy
+    #[serde(default)]
+    pub session_store: SessionStoreConfig,
     pub servers: HashMap<String, Server>,
 }
 
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct StaticCacheConfig {
+    pub size_limit: usize, // kb, 0 désactive le cache
+    pub file_limit: usize, // kb, taille maximale d'un fichier mis en cache
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStorage {
+    #[default]
+    Memory,
+    File, // un fichier par session dans `directory`, conservé entre deux démarrages
+}
+
+/// Prolongation d'une session à chaque requête (sliding) ou non (absolute).
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionExpiry {
+    #[default]
+    Sliding,
+    Absolute, // expire une durée de vie après sa création, même si elle est utilisée
+}
+
+pub fn default_sweep_interval() -> u64 {
+    60
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SessionStoreConfig {
+    #[serde(default)]
+    pub storage: SessionStorage,
+    #[serde(default)]
+    pub directory: String,
+    #[serde(default)]
+    pub expiry: SessionExpiry,
+    #[serde(default = "default_sweep_interval")]
+    pub sweep_interval: u64, // secondes entre deux purges des sessions expirées, 0 = jamais
+}
+
+impl Default for SessionStoreConfig {
+    fn default() -> Self {
+        Self {
+            storage: SessionStorage::default(),
+            directory: String::new(),
+            expiry: SessionExpiry::default(),
+            sweep_interval: default_sweep_interval(),
+        }
+    }
+}
+
+/// Comportement d'un envoi lorsque le fichier cible existe déjà.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    #[default]
+    Overwrite, // remplace (et tronque) le fichier existant
+    Reject,    // 409 Conflict
+    Rename,    // "file (1).png"
+}
+
+/// Règles d'envoi de fichiers pour `location` et ses sous-dossiers.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct UploadPolicy {
+    pub location: String,
+    #[serde(default)]
+    pub collision: CollisionPolicy,
+    #[serde(default)]
+    pub allowed_extensions: Vec<String>, // vide = toutes
+    #[serde(default)]
+    pub denied_extensions: Vec<String>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Redirection {
     pub source: String,
@@ -51,7 +132,12 @@ pub struct Redirection {
 
 pub fn load_config() -> Config {
     let content = fs::read_to_string("src/config.toml").unwrap_or(String::new());
-    toml::from_str(&content).unwrap()
+    let mut config: Config = toml::from_str(&content).unwrap();
+    // Résolution DNS faite ici: elle bloquerait la boucle d'événements
+    for server in config.http.servers.values_mut() {
+        server.resolve_upstreams();
+    }
+    config
 }
 
 pub fn remove_suffix(str: String, suffix: &str) -> String {
@@ -84,4 +170,133 @@ pub fn get_content_length(req: &String) -> Option<String> {
     } else {
         return None;
     }
-}
\ No newline at end of file
+}
+
+/// Décode une composante de requête ou de formulaire ("%20" -> " ", "+" -> " ").
+pub fn url_decode(str: &str) -> String {
+    percent_decode(&str.replace('+', " "))
+}
+
+/// Décode un chemin d'URL ("%20" -> " "); "+" y reste un caractère littéral.
+pub fn percent_decode(str: &str) -> String {
+    let bytes = str.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Résout les segments "." et ".." d'un chemin d'URL décodé sans remonter au-dessus
+/// de la racine ("/a/../../etc/" -> "/etc/"); la barre finale est gardée.
+pub fn normalize_path(path: &str) -> String {
+    let mut segments: Vec<&str> = vec![];
+    let mut directory = false;
+    for segment in path.split('/') {
+        directory = matches!(segment, "" | "." | "..");
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+    match segments.is_empty() {
+        true => "/".to_string(),
+        false if directory => format!("/{}/", segments.join("/")),
+        false => format!("/{}", segments.join("/")),
+    }
+}
+
+/// Décode une chaîne base64 standard (avec ou sans remplissage "=").
+pub fn base64_decode(str: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(str.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in str.trim_end_matches('=').bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+/// Copie un fichier ou un dossier complet (sans suivre les liens symboliques).
+pub fn copy_recursive(source: &Path, target: &Path) -> std::io::Result<()> {
+    let file_type = fs::symlink_metadata(source)?.file_type();
+    if file_type.is_dir() {
+        fs::create_dir(target)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &target.join(entry.file_name()))?;
+        }
+    } else if file_type.is_file() {
+        fs::copy(source, target)?;
+    }
+    Ok(())
+}
+
+/// Encode une composante de chemin pour l'utiliser dans un lien ("a b" -> "a%20b").
+pub fn url_encode(str: &str) -> String {
+    str.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Encode chaque segment d'un chemin d'URL, en gardant les "/".
+pub fn url_encode_path(path: &str) -> String {
+    path.split('/').map(url_encode).collect::<Vec<String>>().join("/")
+}
+
+/// Type MIME d'un fichier d'après son extension.
+pub fn get_mime_type(path: &str) -> &'static str {
+    match Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .as_deref()
+    {
+        Some("html") | Some("htm") => "text/html",
+        Some("css") => "text/css",
+        Some("js") => "application/javascript",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        _ => "text/plain", // Type par défaut
+    }
+}
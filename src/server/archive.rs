@@ -0,0 +1,554 @@
+use chrono::{DateTime, Datelike, Local, Timelike};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// -------------------------------------------------------------------------------------
+// ARCHIVE
+// -------------------------------------------------------------------------------------
+// Quantité de données lues sur le disque à chaque tour
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAR_BLOCK: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Format demandé par `?download=zip|tar.gz`.
+    pub fn from_query(value: &str) -> Option<Self> {
+        match value {
+            "zip" => Some(Self::Zip),
+            "tar.gz" | "tgz" | "tar" => Some(Self::TarGz),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarGz => "application/gzip",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+        }
+    }
+}
+
+/// Fichier ou dossier à placer dans l'archive, `name` étant son chemin relatif dans l'archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_directory: bool,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+#[derive(Debug)]
+struct CurrentFile {
+    entry: ArchiveEntry,
+    file: File,
+    written: u64,
+    crc: crc32fast::Hasher,
+    local_offset: u64,
+}
+
+/// Archive construite à la volée pendant l'envoi, en réponse `Transfer-Encoding: chunked`.
+/// Seul le morceau en cours est gardé en mémoire.
+pub struct ArchiveTransfer {
+    pub format: ArchiveFormat,
+    entries: VecDeque<ArchiveEntry>,
+    current: Option<CurrentFile>,
+    // Octets prêts à partir sur le socket (déjà encodés en chunked)
+    pending: Vec<u8>,
+    gzip: Option<GzEncoder<Vec<u8>>>,
+    // Zip: position dans l'archive et répertoire central accumulé
+    offset: u64,
+    central_directory: Vec<u8>,
+    count: usize,
+    finished: bool,
+}
+
+impl std::fmt::Debug for ArchiveTransfer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArchiveTransfer")
+            .field("format", &self.format)
+            .field("entries", &self.entries.len())
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+impl ArchiveTransfer {
+    /// Prépare l'archive de `entries`, précédée des en-têtes HTTP `head`.
+    pub fn new(format: ArchiveFormat, entries: Vec<ArchiveEntry>, head: Vec<u8>) -> Self {
+        Self {
+            format,
+            entries: entries.into(),
+            current: None,
+            pending: head,
+            gzip: match format {
+                ArchiveFormat::TarGz => Some(GzEncoder::new(vec![], Compression::default())),
+                ArchiveFormat::Zip => None,
+            },
+            offset: 0,
+            central_directory: vec![],
+            count: 0,
+            finished: false,
+        }
+    }
+
+    /// Taille maximale d'une archive zip sans extensions zip64.
+    pub fn zip_limit() -> u64 {
+        u32::MAX as u64
+    }
+
+    /// Écrit autant que possible sans bloquer.
+    /// Renvoie `Ok(true)` lorsque l'archive complète a été envoyée.
+    pub fn advance<W: Write>(&mut self, stream: &mut W) -> io::Result<bool> {
+        loop {
+            while !self.pending.is_empty() {
+                match stream.write(&self.pending) {
+                    Ok(0) => return Err(io::Error::from(ErrorKind::WriteZero)),
+                    Ok(n) => {
+                        self.pending.drain(..n);
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if self.finished {
+                let _ = stream.flush();
+                return Ok(true);
+            }
+            self.produce()?;
+        }
+    }
+
+    /// Produit le prochain morceau de l'archive.
+    fn produce(&mut self) -> io::Result<()> {
+        if let Some(mut current) = self.current.take() {
+            let left = current.entry.size - current.written;
+            let mut buffer = vec![0; left.min(CHUNK_SIZE as u64) as usize];
+            let n = match buffer.is_empty() {
+                true => 0,
+                false => current.file.read(&mut buffer)?,
+            };
+
+            if n > 0 {
+                buffer.truncate(n);
+                current.written += n as u64;
+                current.crc.update(&buffer);
+                self.emit(&buffer)?;
+                self.current = Some(current);
+            } else {
+                self.finish_file(current)?;
+            }
+            return Ok(());
+        }
+
+        match self.entries.pop_front() {
+            Some(entry) => self.start_entry(entry),
+            None => self.finish_archive(),
+        }
+    }
+
+    fn start_entry(&mut self, entry: ArchiveEntry) -> io::Result<()> {
+        // Un fichier devenu illisible est simplement ignoré
+        let file = match entry.is_directory {
+            true => None,
+            false => match File::open(&entry.path) {
+                Ok(file) => Some(file),
+                Err(_) => return Ok(()),
+            },
+        };
+
+        let local_offset = self.offset;
+        let header = match self.format {
+            ArchiveFormat::TarGz => Self::tar_header(&entry)?,
+            ArchiveFormat::Zip => {
+                let header = Self::zip_local_header(&entry);
+                if entry.is_directory {
+                    self.zip_central_record(&entry, 0, 0, local_offset);
+                }
+                header
+            }
+        };
+        self.emit(&header)?;
+
+        if let Some(file) = file {
+            self.current = Some(CurrentFile {
+                entry,
+                file,
+                written: 0,
+                crc: crc32fast::Hasher::new(),
+                local_offset,
+            });
+        }
+        Ok(())
+    }
+
+    fn finish_file(&mut self, current: CurrentFile) -> io::Result<()> {
+        match self.format {
+            ArchiveFormat::TarGz => {
+                // Fichier raccourci pendant la lecture: compléter jusqu'à la taille annoncée
+                let mut missing = current.entry.size - current.written;
+                while missing > 0 {
+                    let size = missing.min(CHUNK_SIZE as u64);
+                    self.emit(&vec![0; size as usize])?;
+                    missing -= size;
+                }
+                let total = current.entry.size as usize;
+                self.emit(&vec![0; (TAR_BLOCK - total % TAR_BLOCK) % TAR_BLOCK])?;
+            }
+            ArchiveFormat::Zip => {
+                let crc = current.crc.finalize();
+                let mut descriptor = vec![];
+                descriptor.extend(0x08074b50u32.to_le_bytes());
+                descriptor.extend(crc.to_le_bytes());
+                descriptor.extend((current.written as u32).to_le_bytes());
+                descriptor.extend((current.written as u32).to_le_bytes());
+                self.emit(&descriptor)?;
+                self.zip_central_record(
+                    &current.entry,
+                    crc,
+                    current.written as u32,
+                    current.local_offset,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn finish_archive(&mut self) -> io::Result<()> {
+        match self.format {
+            ArchiveFormat::TarGz => {
+                self.emit(&[0; TAR_BLOCK * 2])?;
+                if let Some(gzip) = self.gzip.take() {
+                    let tail = gzip.finish()?;
+                    self.chunk(&tail);
+                }
+            }
+            ArchiveFormat::Zip => {
+                let directory = std::mem::take(&mut self.central_directory);
+                let mut end = vec![];
+                end.extend(0x06054b50u32.to_le_bytes());
+                end.extend([0; 4]); // disque courant / disque du répertoire
+                end.extend((self.count as u16).to_le_bytes());
+                end.extend((self.count as u16).to_le_bytes());
+                end.extend((directory.len() as u32).to_le_bytes());
+                end.extend((self.offset as u32).to_le_bytes());
+                end.extend([0; 2]); // commentaire
+                self.emit(&directory)?;
+                self.emit(&end)?;
+            }
+        }
+        // Dernier morceau du chunked encoding
+        self.pending.extend_from_slice(b"0\r\n\r\n");
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Ajoute des octets bruts de l'archive (compressés pour le tar.gz).
+    fn emit(&mut self, data: &[u8]) -> io::Result<()> {
+        self.offset += data.len() as u64;
+        match &mut self.gzip {
+            Some(gzip) => {
+                gzip.write_all(data)?;
+                let compressed = std::mem::take(gzip.get_mut());
+                self.chunk(&compressed);
+            }
+            None => self.chunk(data),
+        }
+        Ok(())
+    }
+
+    fn chunk(&mut self, data: &[u8]) {
+        if !data.is_empty() {
+            self.pending
+                .extend_from_slice(format!("{:X}\r\n", data.len()).as_bytes());
+            self.pending.extend_from_slice(data);
+            self.pending.extend_from_slice(b"\r\n");
+        }
+    }
+
+    fn tar_header(entry: &ArchiveEntry) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        let name = match entry.is_directory {
+            true => format!("{}/", entry.name),
+            false => entry.name.clone(),
+        };
+
+        // Nom trop long pour l'en-tête: entrée GNU "././@LongLink" le précédant
+        if name.len() >= 100 {
+            let mut long = tar::Header::new_gnu();
+            long.set_path("././@LongLink")?;
+            long.set_entry_type(tar::EntryType::GNULongName);
+            long.set_size(name.len() as u64 + 1);
+            long.set_mode(0o644);
+            long.set_cksum();
+            bytes.extend_from_slice(long.as_bytes());
+            let mut data = name.clone().into_bytes();
+            data.push(0);
+            data.resize(data.len().div_ceil(TAR_BLOCK) * TAR_BLOCK, 0);
+            bytes.extend(data);
+        }
+
+        let mut header = tar::Header::new_gnu();
+        if name.len() < 100 {
+            header.set_path(&name)?;
+        } else if let Some(gnu) = header.as_gnu_mut() {
+            gnu.name[..99].copy_from_slice(&name.as_bytes()[..99]);
+        }
+        match entry.is_directory {
+            true => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+            }
+            false => {
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(0o644);
+                header.set_size(entry.size);
+            }
+        }
+        let mtime = entry
+            .modified
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        header.set_mtime(mtime);
+        header.set_cksum();
+        bytes.extend_from_slice(header.as_bytes());
+        Ok(bytes)
+    }
+
+    fn zip_name(entry: &ArchiveEntry) -> String {
+        match entry.is_directory {
+            true => format!("{}/", entry.name),
+            false => entry.name.clone(),
+        }
+    }
+
+    /// Date et heure au format MS-DOS utilisé par le zip.
+    fn dos_datetime(modified: SystemTime) -> (u16, u16) {
+        let date: DateTime<Local> = modified.into();
+        let time = ((date.hour() << 11) | (date.minute() << 5) | (date.second() / 2)) as u16;
+        let year = (date.year().clamp(1980, 2107) - 1980) as u32;
+        let day = (year << 9) | (date.month() << 5) | date.day();
+        (time, day as u16)
+    }
+
+    fn zip_flags(entry: &ArchiveEntry) -> u16 {
+        // bit 11: noms UTF-8, bit 3: CRC et tailles dans le descripteur qui suit les données
+        match entry.is_directory {
+            true => 0x0800,
+            false => 0x0808,
+        }
+    }
+
+    fn zip_local_header(entry: &ArchiveEntry) -> Vec<u8> {
+        let name = Self::zip_name(entry);
+        let (time, date) = Self::dos_datetime(entry.modified);
+        let mut header = vec![];
+        header.extend(0x04034b50u32.to_le_bytes());
+        header.extend(20u16.to_le_bytes()); // version nécessaire
+        header.extend(Self::zip_flags(entry).to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // méthode: stocké sans compression
+        header.extend(time.to_le_bytes());
+        header.extend(date.to_le_bytes());
+        header.extend([0; 12]); // crc et tailles connus seulement à la fin
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes());
+        header.extend(name.as_bytes());
+        header
+    }
+
+    fn zip_central_record(&mut self, entry: &ArchiveEntry, crc: u32, size: u32, offset: u64) {
+        let name = Self::zip_name(entry);
+        let (time, date) = Self::dos_datetime(entry.modified);
+        let attributes: u32 = match entry.is_directory {
+            true => (0o40755 << 16) | 0x10,
+            false => 0o100644 << 16,
+        };
+
+        let record = &mut self.central_directory;
+        record.extend(0x02014b50u32.to_le_bytes());
+        record.extend(((3u16 << 8) | 20).to_le_bytes()); // créé sous unix
+        record.extend(20u16.to_le_bytes());
+        record.extend(Self::zip_flags(entry).to_le_bytes());
+        record.extend(0u16.to_le_bytes());
+        record.extend(time.to_le_bytes());
+        record.extend(date.to_le_bytes());
+        record.extend(crc.to_le_bytes());
+        record.extend(size.to_le_bytes());
+        record.extend(size.to_le_bytes());
+        record.extend((name.len() as u16).to_le_bytes());
+        record.extend([0; 8]); // extra, commentaire, disque, attributs internes
+        record.extend(attributes.to_le_bytes());
+        record.extend((offset as u32).to_le_bytes());
+        record.extend(name.as_bytes());
+        self.count += 1;
+    }
+}
+// -------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Server;
+    use flate2::read::GzDecoder;
+    use regex::RegexSet;
+    use std::fs;
+
+    /// Retire le chunked encoding d'une réponse sans en-têtes.
+    fn dechunk(mut data: &[u8]) -> Vec<u8> {
+        let mut body = vec![];
+        loop {
+            let line_end = data.windows(2).position(|w| w == b"\r\n").unwrap();
+            let size = usize::from_str_radix(std::str::from_utf8(&data[..line_end]).unwrap(), 16).unwrap();
+            if size == 0 {
+                return body;
+            }
+            body.extend_from_slice(&data[line_end + 2..line_end + 2 + size]);
+            data = &data[line_end + 4 + size..];
+        }
+    }
+
+    /// Relit un zip depuis son répertoire central: (nom, CRC annoncé, contenu) des entrées.
+    fn read_zip(zip: &[u8]) -> Vec<(String, u32, Vec<u8>)> {
+        let u16_at = |pos: usize| u16::from_le_bytes([zip[pos], zip[pos + 1]]) as usize;
+        let u32_at = |pos: usize| u32::from_le_bytes(zip[pos..pos + 4].try_into().unwrap());
+        let end = zip.len() - 22;
+        assert_eq!(u32_at(end), 0x06054b50);
+        let count = u16_at(end + 10);
+        let mut pos = u32_at(end + 16) as usize;
+        assert_eq!(pos + u32_at(end + 12) as usize, end);
+
+        let mut files = vec![];
+        for _ in 0..count {
+            assert_eq!(u32_at(pos), 0x02014b50);
+            let crc = u32_at(pos + 16);
+            let size = u32_at(pos + 20) as usize;
+            let name_len = u16_at(pos + 28);
+            let name = String::from_utf8(zip[pos + 46..pos + 46 + name_len].to_vec()).unwrap();
+            let offset = u32_at(pos + 42) as usize;
+
+            // En-tête local du même nom, données stockées puis descripteur pour les fichiers
+            assert_eq!(u32_at(offset), 0x04034b50);
+            let local_len = u16_at(offset + 26);
+            assert_eq!(&zip[offset + 30..offset + 30 + local_len], name.as_bytes());
+            let start = offset + 30 + local_len + u16_at(offset + 28);
+            if !name.ends_with('/') {
+                assert_eq!(u32_at(start + size), 0x08074b50);
+                assert_eq!(u32_at(start + size + 4), crc);
+            }
+            files.push((name, crc, zip[start..start + size].to_vec()));
+            pos += 46 + name_len + u16_at(pos + 30) + u16_at(pos + 32);
+        }
+        assert_eq!(pos, end);
+        files
+    }
+
+    #[test]
+    fn test_tar_gz_archive() {
+        let dir = std::env::temp_dir().join(format!("archive_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/hello.txt"), b"Hello World").unwrap();
+
+        let path = dir.join("sub/hello.txt");
+        let entries = vec![
+            ArchiveEntry {
+                path: dir.join("sub"),
+                name: "sub".to_string(),
+                is_directory: true,
+                size: 0,
+                modified: SystemTime::now(),
+            },
+            ArchiveEntry {
+                size: fs::metadata(&path).unwrap().len(),
+                path,
+                name: "sub/hello.txt".to_string(),
+                is_directory: false,
+                modified: SystemTime::now(),
+            },
+        ];
+
+        let mut output = vec![];
+        let mut transfer = ArchiveTransfer::new(ArchiveFormat::TarGz, entries, vec![]);
+        assert!(transfer.advance(&mut output).unwrap());
+
+        let body = dechunk(&output);
+        let mut archive = tar::Archive::new(GzDecoder::new(&body[..]));
+        let mut names = vec![];
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().to_string();
+            if name == "sub/hello.txt" {
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                assert_eq!(content, "Hello World");
+            }
+            names.push(name);
+        }
+        assert_eq!(names, vec!["sub/", "sub/hello.txt"]);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_zip_archive() {
+        let dir = std::env::temp_dir().join(format!("archive_zip_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(dir.join("empty")).unwrap();
+        fs::write(dir.join("sub/hello.txt"), b"Hello World").unwrap();
+        fs::write(dir.join("top.txt"), b"top").unwrap();
+        fs::write(dir.join("secret.txt"), b"hidden").unwrap();
+
+        let server = Server::new(
+            "127.0.0.1".to_string(),
+            "localhost".to_string(),
+            vec![8080],
+            dir.to_string_lossy().to_string(),
+            String::new(),
+            String::new(),
+            0,
+            vec![],
+            true,
+            vec![],
+            vec!["^secret".to_string()],
+        );
+        let re = RegexSet::new(&server.exclusion).unwrap();
+        let mut entries = vec![];
+        server
+            .collect_archive_entries(&re, &dir, "", &mut entries)
+            .unwrap();
+
+        let mut output = vec![];
+        let mut transfer = ArchiveTransfer::new(ArchiveFormat::Zip, entries, vec![]);
+        assert!(transfer.advance(&mut output).unwrap());
+
+        let mut files = read_zip(&dechunk(&output));
+        files.sort();
+        let names: Vec<&str> = files.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["empty/", "sub/", "sub/hello.txt", "top.txt"]);
+        for (_, crc, content) in &files {
+            assert_eq!(*crc, crc32fast::hash(content));
+        }
+        assert!(files[0].2.is_empty());
+        assert_eq!(files[2].2, b"Hello World");
+        assert_eq!(files[3].2, b"top");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,933 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use std::{fs, io};
+
+use super::{
+    is_under, RawResponse, Request, ScgiProtocol, Server, ServerError, ServerState, Transfer,
+};
+use crate::{Config, StaticCacheConfig};
+use mio::net::TcpStream;
+
+// -------------------------------------------------------------------------------------
+// STATIC CACHE
+// -------------------------------------------------------------------------------------
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub content: Vec<u8>,
+    pub modified: Option<SystemTime>,
+    pub length: u64,
+    pub etag: String,
+    last_used: u64,
+}
+
+/// Cache LRU du contenu des fichiers statiques, indexé par chemin canonique.
+#[derive(Debug, Default)]
+pub struct StaticCache {
+    pub entries: HashMap<PathBuf, CacheEntry>,
+    pub size_limit: usize,
+    pub file_limit: usize,
+    pub used: usize,
+    pub hits: u64,
+    pub misses: u64,
+    tick: u64,
+}
+
+impl StaticCache {
+    /// Crée un cache à partir de la configuration (tailles en kb, 0 = cache désactivé).
+    pub fn new(config: &StaticCacheConfig) -> Self {
+        Self {
+            size_limit: config.size_limit * 1024,
+            file_limit: config.file_limit * 1024,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.size_limit > 0
+    }
+
+    /// Renvoie l'entrée du fichier, en la (re)chargeant si elle est absente ou périmée,
+    /// et vrai si elle était déjà en cache (HIT).
+    /// `Ok(None)` signifie que le fichier est trop gros pour être mis en cache.
+    pub fn get(&mut self, path: &str) -> io::Result<Option<(&CacheEntry, bool)>> {
+        let key = fs::canonicalize(path)?;
+        let metadata = fs::metadata(&key)?;
+        let modified = metadata.modified().ok();
+        self.tick += 1;
+
+        // Invalidation si le fichier a changé depuis sa mise en cache
+        let fresh = match self.entries.get(&key) {
+            Some(entry) => entry.modified == modified && entry.length == metadata.len(),
+            None => false,
+        };
+
+        if fresh {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+            self.remove(&key);
+
+            let length = metadata.len() as usize;
+            if length > self.file_limit || length > self.size_limit {
+                return Ok(None);
+            }
+
+            let content = fs::read(&key)?;
+            self.evict(content.len());
+            self.used += content.len();
+            self.entries.insert(
+                key.clone(),
+                CacheEntry {
+                    etag: Self::make_etag(&content),
+                    length: content.len() as u64,
+                    content,
+                    modified,
+                    last_used: 0,
+                },
+            );
+        }
+
+        let entry = self.entries.get_mut(&key).map(|entry| {
+            entry.last_used = self.tick;
+            (&*entry, fresh)
+        });
+        Ok(entry)
+    }
+
+    /// Calcule un ETag fort à partir du contenu du fichier.
+    pub fn make_etag(content: &[u8]) -> String {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        format!("\"{:016x}-{:x}\"", hasher.finish(), content.len())
+    }
+
+    fn remove(&mut self, key: &Path) {
+        if let Some(old) = self.entries.remove(key) {
+            self.used -= old.content.len();
+        }
+    }
+
+    /// Libère les entrées les moins récemment utilisées jusqu'à pouvoir stocker `needed` octets.
+    fn evict(&mut self, needed: usize) {
+        while self.used + needed > self.size_limit {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => self.remove(&key),
+                None => break,
+            }
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
+
+// -------------------------------------------------------------------------------------
+// RESPONSE CACHE
+// -------------------------------------------------------------------------------------
+// Variantes (Vary) gardées au plus pour une même clé
+const CACHE_VARIANTS_MAX: usize = 16;
+// Statuts pouvant être gardés sans durée explicite (RFC 9111, section 4.2.2)
+const HEURISTIC_STATUSES: [u16; 8] = [200, 203, 204, 300, 301, 308, 404, 410];
+
+pub fn default_cache_key() -> String {
+    "$method $host$request_uri".to_string()
+}
+
+pub fn default_cache_size() -> usize {
+    10240
+}
+
+pub fn default_cache_entry_limit() -> usize {
+    1024
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStorage {
+    #[default]
+    Memory,
+    Disk, // un fichier par clé dans `directory`, conservé entre deux démarrages
+}
+
+/// Cache des réponses de scripts CGI, d'applications FastCGI/SCGI/uwsgi et de serveurs
+/// amont pour `location` et ses sous-dossiers (requêtes GET sans Authorization).
+#[derive(Debug, Deserialize, Clone)]
+pub struct ResponseCacheLocation {
+    pub location: String,
+    #[serde(default)]
+    pub storage: CacheStorage,
+    #[serde(default)]
+    pub directory: String, // dossier des entrées en mode disk
+    // "$method $host$request_uri": $uri, $args, $http_<en-tête>, $cookie_<nom>...
+    #[serde(default = "default_cache_key")]
+    pub key: String,
+    #[serde(default)]
+    pub default_ttl: u64, // secondes sans Cache-Control ni Expires, 0 = pas gardée
+    #[serde(default)]
+    pub stale_while_revalidate: u64, // secondes si la réponse ne le précise pas
+    #[serde(default = "default_cache_size")]
+    pub max_size: usize, // kb en mémoire pour la location
+    #[serde(default = "default_cache_entry_limit")]
+    pub entry_limit: usize, // kb, taille maximale d'une réponse gardée
+}
+
+impl ResponseCacheLocation {
+    /// Clé de la requête d'après le modèle `key`.
+    pub fn cache_key(&self, request: &Request) -> String {
+        let mut key = String::new();
+        let mut rest = self.key.as_str();
+        while let Some(start) = rest.find('$') {
+            key += &rest[..start];
+            rest = &rest[start + 1..];
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            key += &Self::variable(&rest[..end], request);
+            rest = &rest[end..];
+        }
+        key + rest
+    }
+
+    fn variable(name: &str, request: &Request) -> String {
+        let header = |name: &str| request.header(name).cloned().unwrap_or_default();
+        match name {
+            "method" => request.method.clone(),
+            "host" => header("Host"),
+            "uri" => request.path.clone(),
+            "args" => request
+                .location
+                .split_once('?')
+                .map_or("", |(_, query)| query)
+                .to_string(),
+            "request_uri" => request.location.clone(),
+            _ => {
+                if let Some(name) = name.strip_prefix("http_") {
+                    return header(&name.replace('_', "-"));
+                }
+                if let Some(name) = name.strip_prefix("cookie_") {
+                    return request.cookies.get(name).cloned().unwrap_or_default();
+                }
+                format!("${}", name)
+            }
+        }
+    }
+
+    /// Durées (secondes) pendant lesquelles la réponse est fraîche puis servie périmée
+    /// le temps de la revalider, `None` si elle ne doit pas être gardée.
+    pub fn freshness(&self, status: u16, headers: &[(String, String)]) -> Option<(i64, i64)> {
+        let header = |wanted: &str| {
+            headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+                .map(|(_, value)| value.as_str())
+        };
+        if header("Set-Cookie").is_some() || header("Vary").is_some_and(|vary| vary.contains('*')) {
+            return None;
+        }
+
+        let directives: Vec<(String, Option<String>)> = header("Cache-Control")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty())
+            .map(|directive| match directive.split_once('=') {
+                Some((name, value)) => (
+                    name.trim().to_ascii_lowercase(),
+                    Some(value.trim().trim_matches('"').to_string()),
+                ),
+                None => (directive.to_ascii_lowercase(), None),
+            })
+            .collect();
+        let directive = |wanted: &str| {
+            directives
+                .iter()
+                .find(|(name, _)| name == wanted)
+                .map(|(_, value)| value.clone().unwrap_or_default())
+        };
+        if ["no-store", "no-cache", "private"]
+            .iter()
+            .any(|name| directive(name).is_some())
+        {
+            return None;
+        }
+
+        let seconds = |value: String| value.parse::<i64>().ok();
+        let date = |name: &str| {
+            header(name)
+                .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+                .map(|date| date.timestamp())
+        };
+        // Fraîcheur fixée par la réponse elle-même (Cache-Control ou Expires)
+        let explicit = directive("s-maxage").is_some()
+            || directive("max-age").is_some()
+            || header("Expires").is_some();
+        let ttl = match directive("s-maxage").or_else(|| directive("max-age")) {
+            Some(age) => seconds(age).unwrap_or(0),
+            None => match header("Expires") {
+                // Une date invalide ("0") signifie déjà expirée
+                Some(_) => date("Expires").map_or(0, |expires| {
+                    expires - date("Date").unwrap_or_else(|| Utc::now().timestamp())
+                }),
+                None if HEURISTIC_STATUSES.contains(&status) => self.default_ttl as i64,
+                None => 0,
+            },
+        }
+        .max(0);
+        let stale = match directive("must-revalidate").or_else(|| directive("proxy-revalidate")) {
+            Some(_) => 0,
+            None => directive("stale-while-revalidate")
+                .and_then(seconds)
+                .unwrap_or(self.stale_while_revalidate as i64)
+                .max(0),
+        };
+        // stale-while-revalidate seul ne rend pas gardable une réponse sans fraîcheur
+        ((ttl > 0 || explicit) && ttl + stale > 0).then_some((ttl, stale))
+    }
+}
+
+/// Réponse gardée; le corps est écrit à la suite des métadonnées en mode disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub vary: Vec<(String, String)>, // en-têtes de requête cités par Vary et leur valeur
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    #[serde(skip)]
+    pub body: Vec<u8>,
+    pub body_length: usize,
+    pub stored_at: i64,
+    pub fresh_until: i64,
+    pub stale_until: i64,
+}
+
+impl CachedResponse {
+    fn matches(&self, request: &Request) -> bool {
+        self.vary.iter().all(|(name, value)| {
+            request.header(name).map(String::as_str).unwrap_or_default() == value
+        })
+    }
+}
+
+/// Réponse d'un script ou d'un serveur amont recopiée au fil de son envoi au client.
+#[derive(Debug)]
+pub struct CacheCapture {
+    pub location: ResponseCacheLocation,
+    pub key: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub complete: bool,
+    too_large: bool,
+}
+
+impl CacheCapture {
+    pub fn new(location: &ResponseCacheLocation, key: &str) -> Self {
+        Self {
+            location: location.clone(),
+            key: key.to_string(),
+            status: 0,
+            headers: vec![],
+            body: vec![],
+            complete: false,
+            too_large: false,
+        }
+    }
+
+    pub fn append(&mut self, data: &[u8]) {
+        if self.too_large {
+            return;
+        }
+        if self.body.len() + data.len() > self.location.entry_limit * 1024 {
+            self.too_large = true;
+            self.body = vec![];
+            return;
+        }
+        self.body.extend_from_slice(data);
+    }
+}
+
+pub enum CacheLookup {
+    Fresh(CachedResponse),
+    /// Périmée mais utilisable pendant sa revalidation (stale-while-revalidate)
+    Stale(CachedResponse),
+    Miss,
+}
+
+/// Entrées de disque: les métadonnées sur la première ligne, puis les corps.
+#[derive(Debug, Serialize, Deserialize)]
+struct DiskRecord {
+    key: String,
+    variants: Vec<CachedResponse>,
+}
+
+#[derive(Debug)]
+struct MemoryRecord {
+    location: String,
+    variants: Vec<CachedResponse>,
+    size: usize,
+    last_used: u64,
+}
+
+/// Réponses dynamiques gardées, en mémoire (LRU par location) ou sur disque.
+#[derive(Debug, Default)]
+pub struct ResponseCache {
+    memory: HashMap<String, MemoryRecord>,
+    used: HashMap<String, usize>,
+    // Clés en cours de revalidation, et requêtes correspondantes à lancer par le Router
+    revalidating: HashSet<String>,
+    background: Vec<Transfer>,
+    pub hits: u64,
+    pub misses: u64,
+    tick: u64,
+}
+
+impl ResponseCache {
+    /// Cherche la variante de la requête sous `key`.
+    pub fn lookup(
+        &mut self,
+        location: &ResponseCacheLocation,
+        key: &str,
+        request: &Request,
+    ) -> CacheLookup {
+        let now = Utc::now().timestamp();
+        let entry = self
+            .load(location, key)
+            .into_iter()
+            .find(|entry| entry.matches(request));
+        match entry {
+            Some(entry) if now < entry.fresh_until => {
+                self.hits += 1;
+                CacheLookup::Fresh(entry)
+            }
+            Some(entry) if now < entry.stale_until => {
+                self.hits += 1;
+                CacheLookup::Stale(entry)
+            }
+            _ => {
+                self.misses += 1;
+                CacheLookup::Miss
+            }
+        }
+    }
+
+    /// Garde la réponse recopiée si elle est complète et que ses en-têtes le permettent.
+    pub fn store(&mut self, capture: CacheCapture, request: &Request) {
+        let location = &capture.location;
+        self.revalidating
+            .remove(&Self::record_key(location, &capture.key));
+        if !capture.complete || capture.too_large {
+            return;
+        }
+        let Some((ttl, stale)) = location.freshness(capture.status, &capture.headers) else {
+            return;
+        };
+
+        let vary = capture
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Vary"))
+            .flat_map(|(_, value)| value.split(','))
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let value = request.header(&name).cloned().unwrap_or_default();
+                (name, value)
+            })
+            .collect();
+        let now = Utc::now().timestamp();
+        let entry = CachedResponse {
+            vary,
+            status: capture.status,
+            headers: capture.headers.clone(),
+            body_length: capture.body.len(),
+            body: capture.body,
+            stored_at: now,
+            fresh_until: now + ttl,
+            stale_until: now + ttl + stale,
+        };
+
+        let mut variants = self.load(location, &capture.key);
+        variants.retain(|variant| variant.vary != entry.vary);
+        variants.push(entry);
+        if variants.len() > CACHE_VARIANTS_MAX {
+            variants.remove(0);
+        }
+        self.save(location, &capture.key, variants);
+    }
+
+    /// Marque la clé comme en cours de revalidation. Renvoie `false` si elle l'est déjà.
+    pub fn start_revalidation(&mut self, location: &ResponseCacheLocation, key: &str) -> bool {
+        self.revalidating.insert(Self::record_key(location, key))
+    }
+
+    pub fn cancel_revalidation(&mut self, location: &ResponseCacheLocation, key: &str) {
+        self.revalidating.remove(&Self::record_key(location, key));
+    }
+
+    /// Requête de revalidation à faire avancer par le Router sans client.
+    pub fn push_background(&mut self, transfer: Transfer) {
+        self.background.push(transfer);
+    }
+
+    pub fn take_background(&mut self) -> Vec<Transfer> {
+        std::mem::take(&mut self.background)
+    }
+
+    fn record_key(location: &ResponseCacheLocation, key: &str) -> String {
+        format!("{} {}", location.location, key)
+    }
+
+    fn disk_path(location: &ResponseCacheLocation, key: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        Path::new(&location.directory).join(format!("{:016x}.cache", hasher.finish()))
+    }
+
+    fn load(&mut self, location: &ResponseCacheLocation, key: &str) -> Vec<CachedResponse> {
+        match location.storage {
+            CacheStorage::Memory => {
+                self.tick += 1;
+                match self.memory.get_mut(&Self::record_key(location, key)) {
+                    Some(record) => {
+                        record.last_used = self.tick;
+                        record.variants.clone()
+                    }
+                    None => vec![],
+                }
+            }
+            CacheStorage::Disk => Self::read_disk(&Self::disk_path(location, key))
+                .ok()
+                .filter(|record| record.key == key)
+                .map(|record| record.variants)
+                .unwrap_or_default(),
+        }
+    }
+
+    fn save(&mut self, location: &ResponseCacheLocation, key: &str, variants: Vec<CachedResponse>) {
+        match location.storage {
+            CacheStorage::Memory => {
+                let record_key = Self::record_key(location, key);
+                self.remove(&record_key);
+                let size = variants.iter().map(|variant| variant.body.len()).sum();
+                if size > location.max_size * 1024 {
+                    return;
+                }
+                self.evict(&location.location, size, location.max_size * 1024);
+                self.tick += 1;
+                *self.used.entry(location.location.clone()).or_default() += size;
+                self.memory.insert(
+                    record_key,
+                    MemoryRecord {
+                        location: location.location.clone(),
+                        variants,
+                        size,
+                        last_used: self.tick,
+                    },
+                );
+            }
+            CacheStorage::Disk => {
+                let record = DiskRecord {
+                    key: key.to_string(),
+                    variants,
+                };
+                // Échec d'écriture: la réponse n'est simplement pas gardée
+                let _ = Self::write_disk(&Self::disk_path(location, key), &record);
+            }
+        }
+    }
+
+    fn read_disk(path: &Path) -> io::Result<DiskRecord> {
+        let content = fs::read(path)?;
+        let end = content
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+        let mut record: DiskRecord = serde_json::from_slice(&content[..end])?;
+        let mut offset = end + 1;
+        for variant in &mut record.variants {
+            let body = content
+                .get(offset..offset + variant.body_length)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            variant.body = body.to_vec();
+            offset += variant.body_length;
+        }
+        Ok(record)
+    }
+
+    fn write_disk(path: &Path, record: &DiskRecord) -> io::Result<()> {
+        let mut content = serde_json::to_vec(record)?;
+        content.push(b'\n');
+        for variant in &record.variants {
+            content.extend_from_slice(&variant.body);
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Écrit à côté puis renommé: une lecture ne voit jamais une entrée à moitié écrite
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, content)?;
+        fs::rename(&temporary, path)
+    }
+
+    fn remove(&mut self, record_key: &str) {
+        if let Some(old) = self.memory.remove(record_key) {
+            if let Some(used) = self.used.get_mut(&old.location) {
+                *used -= old.size;
+            }
+        }
+    }
+
+    /// Libère les entrées les moins récemment utilisées de la location pour loger `needed`.
+    fn evict(&mut self, location: &str, needed: usize, limit: usize) {
+        while self.used.get(location).copied().unwrap_or(0) + needed > limit {
+            let oldest = self
+                .memory
+                .iter()
+                .filter(|(_, record)| record.location == location)
+                .min_by_key(|(_, record)| record.last_used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => self.remove(&key),
+                None => break,
+            }
+        }
+    }
+}
+
+impl Server {
+    /// Location de cache de la requête: seules les requêtes GET sans Authorization ni
+    /// données de session (réponses personnalisées) en ont.
+    pub fn cache_location(&self, request: &Request) -> Option<&ResponseCacheLocation> {
+        if request.method != "GET"
+            || request.header("Authorization").is_some()
+            || !request.session.is_empty()
+        {
+            return None;
+        }
+        self.cache_locations
+            .iter()
+            .filter(|cache| is_under(&request.path, &cache.location))
+            .max_by_key(|cache| cache.location.trim_end_matches('/').len())
+    }
+
+    /// Envoie une réponse gardée avec son âge et `X-Cache` (HIT ou STALE).
+    pub fn send_cached(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        config: &Config,
+        cookie: &String,
+        entry: CachedResponse,
+        status: &str,
+    ) -> Option<Transfer> {
+        let age = (Utc::now().timestamp() - entry.stored_at).max(0);
+        let mut response = RawResponse::status(entry.status);
+        response.headers = entry.headers;
+        response = response
+            .header("Age", &age.to_string())
+            .header("X-Cache", status);
+        response.body = entry.body;
+        self.send_raw_response(stream, request, config, cookie, response)
+    }
+
+    /// Relance la requête d'une entrée périmée sans client: sa réponse, recopiée dans
+    /// `capture`, remplacera l'entrée. Seuls les scripts et serveurs amont en produisent.
+    pub fn revalidate(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        cookie: &str,
+        config: &Config,
+        state: &mut ServerState,
+        capture: CacheCapture,
+    ) {
+        let (cache, key) = (&capture.location, capture.key.as_str());
+        if !state.response_cache.start_revalidation(cache, key) {
+            return;
+        }
+        let transfer = if let Some(location) = self.proxy_location(&request.path) {
+            self.handle_proxy(stream, request, location, cookie, &mut state.upstreams)
+        } else if let Some((script, gateway)) =
+            self.gateway_script(&self.fastcgi_locations, &request.path)
+        {
+            self.handle_fastcgi(stream, request, &script, &gateway, cookie)
+        } else if let Some((script, gateway)) =
+            self.gateway_script(&self.scgi_locations, &request.path)
+        {
+            self.handle_scgi(
+                stream,
+                request,
+                &script,
+                &gateway,
+                ScgiProtocol::Scgi,
+                cookie,
+            )
+        } else if let Some((script, gateway)) =
+            self.gateway_script(&self.uwsgi_locations, &request.path)
+        {
+            self.handle_scgi(
+                stream,
+                request,
+                &script,
+                &gateway,
+                ScgiProtocol::Uwsgi,
+                cookie,
+            )
+        } else {
+            // Échec de lancement: l'entrée périmée reste servie jusqu'à la prochaine tentative
+            self.cgi_script(&request.path).and_then(|script| {
+                self.cgi_transfer(stream, request, &script, cookie)
+                    .map_err(|e| {
+                        Self::error_log(
+                            request,
+                            config,
+                            "revalidate",
+                            file!(),
+                            line!(),
+                            ServerError::IOError(&e),
+                        )
+                    })
+                    .ok()
+            })
+        };
+
+        match transfer {
+            Some(mut transfer) => {
+                if let Some(response) = transfer.cgi_response() {
+                    response.capture = Some(capture);
+                }
+                state.response_cache.push_background(transfer);
+            }
+            None => state.response_cache.cancel_revalidation(cache, key),
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(storage: CacheStorage, directory: &str) -> ResponseCacheLocation {
+        ResponseCacheLocation {
+            location: "/api".to_string(),
+            storage,
+            directory: directory.to_string(),
+            key: default_cache_key(),
+            default_ttl: 0,
+            stale_while_revalidate: 0,
+            max_size: default_cache_size(),
+            entry_limit: default_cache_entry_limit(),
+        }
+    }
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        let mut request = Request::default();
+        request.method = "GET".to_string();
+        request.path = "/api/items".to_string();
+        request.location = "/api/items?page=2".to_string();
+        for (name, value) in headers {
+            request.headers.insert(name.to_string(), value.to_string());
+        }
+        request.cookies = Request::parse_cookies(request.header("Cookie").map_or("", |c| c));
+        request
+    }
+
+    fn capture(loc: &ResponseCacheLocation, headers: &[(&str, &str)], body: &[u8]) -> CacheCapture {
+        let mut capture = CacheCapture::new(loc, "key");
+        capture.status = 200;
+        capture.headers = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        capture.append(body);
+        capture.complete = true;
+        capture
+    }
+
+    fn headers(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let mut loc = location(CacheStorage::Memory, "");
+        let request = request(&[("Host", "example.com"), ("Cookie", "a=1; lang=fr")]);
+        assert_eq!(loc.cache_key(&request), "GET example.com/api/items?page=2");
+        loc.key = "$uri|$args|$cookie_lang|$http_accept_language|$unknown".to_string();
+        assert_eq!(loc.cache_key(&request), "/api/items|page=2|fr||$unknown");
+    }
+
+    #[test]
+    fn test_freshness() {
+        let mut loc = location(CacheStorage::Memory, "");
+        let fresh = |loc: &ResponseCacheLocation, status, list: &[(&str, &str)]| {
+            loc.freshness(status, &headers(list))
+        };
+        assert_eq!(
+            fresh(&loc, 200, &[("Cache-Control", "max-age=60")]),
+            Some((60, 0))
+        );
+        assert_eq!(
+            fresh(
+                &loc,
+                200,
+                &[(
+                    "cache-control",
+                    "max-age=60, s-maxage=10, stale-while-revalidate=5"
+                )]
+            ),
+            Some((10, 5))
+        );
+        assert_eq!(
+            fresh(&loc, 200, &[("Cache-Control", "no-store, max-age=60")]),
+            None
+        );
+        assert_eq!(fresh(&loc, 200, &[("Cache-Control", "private")]), None);
+        assert_eq!(
+            fresh(
+                &loc,
+                200,
+                &[("Cache-Control", "max-age=60"), ("Set-Cookie", "a=1")]
+            ),
+            None
+        );
+        assert_eq!(
+            fresh(&loc, 200, &[("Cache-Control", "max-age=60"), ("Vary", "*")]),
+            None
+        );
+        assert_eq!(
+            fresh(
+                &loc,
+                200,
+                &[
+                    ("Date", "Mon, 19 Oct 2026 10:00:00 GMT"),
+                    ("Expires", "Mon, 19 Oct 2026 10:02:00 GMT")
+                ]
+            ),
+            Some((120, 0))
+        );
+        assert_eq!(fresh(&loc, 200, &[("Expires", "0")]), None);
+
+        // Durées par défaut de la location
+        assert_eq!(fresh(&loc, 200, &[]), None);
+        loc.default_ttl = 30;
+        loc.stale_while_revalidate = 15;
+        assert_eq!(fresh(&loc, 200, &[]), Some((30, 15)));
+        assert_eq!(fresh(&loc, 500, &[]), None);
+        assert_eq!(
+            fresh(
+                &loc,
+                500,
+                &[("Cache-Control", "max-age=0, stale-while-revalidate=20")]
+            ),
+            Some((0, 20))
+        );
+        assert_eq!(
+            fresh(
+                &loc,
+                200,
+                &[("Cache-Control", "max-age=5, must-revalidate")]
+            ),
+            Some((5, 0))
+        );
+    }
+
+    #[test]
+    fn test_memory_store_and_vary() {
+        let loc = location(CacheStorage::Memory, "");
+        let mut cache = ResponseCache::default();
+        let french = request(&[("Accept-Language", "fr")]);
+        let english = request(&[("Accept-Language", "en")]);
+        assert!(matches!(
+            cache.lookup(&loc, "key", &french),
+            CacheLookup::Miss
+        ));
+
+        let list = [("Cache-Control", "max-age=60"), ("Vary", "Accept-Language")];
+        cache.store(capture(&loc, &list, b"bonjour"), &french);
+        cache.store(capture(&loc, &list, b"hello"), &english);
+        match cache.lookup(&loc, "key", &french) {
+            CacheLookup::Fresh(entry) => assert_eq!(entry.body, b"bonjour"),
+            _ => panic!("expected a fresh entry"),
+        }
+        match cache.lookup(&loc, "key", &english) {
+            CacheLookup::Fresh(entry) => assert_eq!(entry.body, b"hello"),
+            _ => panic!("expected a fresh entry"),
+        }
+        let german = request(&[("Accept-Language", "de")]);
+        assert!(matches!(
+            cache.lookup(&loc, "key", &german),
+            CacheLookup::Miss
+        ));
+
+        // Réponse incomplète ou non gardable: rien n'est remplacé
+        let mut partial = capture(&loc, &list, b"trunc");
+        partial.complete = false;
+        cache.store(partial, &french);
+        cache.store(
+            capture(&loc, &[("Cache-Control", "no-store")], b"x"),
+            &french,
+        );
+        match cache.lookup(&loc, "key", &french) {
+            CacheLookup::Fresh(entry) => assert_eq!(entry.body, b"bonjour"),
+            _ => panic!("expected a fresh entry"),
+        }
+
+        // Une seule revalidation à la fois par clé
+        assert!(cache.start_revalidation(&loc, "key"));
+        assert!(!cache.start_revalidation(&loc, "key"));
+        cache.store(capture(&loc, &list, b"salut"), &french);
+        assert!(cache.start_revalidation(&loc, "key"));
+    }
+
+    #[test]
+    fn test_disk_store() {
+        let dir = std::env::temp_dir().join(format!("response_cache_{}", std::process::id()));
+        let loc = location(CacheStorage::Disk, dir.to_str().unwrap());
+        let request = request(&[]);
+        let mut cache = ResponseCache::default();
+        let list = [("Cache-Control", "max-age=0, stale-while-revalidate=60")];
+        cache.store(capture(&loc, &list, b"line 1\nline 2\n"), &request);
+
+        // Relu par un nouveau cache, comme après un redémarrage
+        let mut cache = ResponseCache::default();
+        match cache.lookup(&loc, "key", &request) {
+            CacheLookup::Stale(entry) => {
+                assert_eq!(entry.body, b"line 1\nline 2\n");
+                assert_eq!(entry.headers, headers(&list));
+            }
+            _ => panic!("expected a stale entry"),
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_static_cache_hit() {
+        let path = std::env::temp_dir().join(format!("static_cache_{}.txt", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        fs::write(&path, b"hello").unwrap();
+        let mut cache = StaticCache::new(&StaticCacheConfig {
+            size_limit: 64,
+            file_limit: 16,
+        });
+        let hit = |cache: &mut StaticCache| cache.get(path_str).unwrap().map(|(_, hit)| hit);
+        assert_eq!(hit(&mut cache), Some(false));
+        assert_eq!(hit(&mut cache), Some(true));
+
+        // Fichier modifié: rechargé
+        fs::write(&path, b"hello world").unwrap();
+        assert_eq!(hit(&mut cache), Some(false));
+        assert_eq!((cache.hits, cache.misses), (1, 2));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,34 +1,1317 @@
-use std::process::Command;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use mio::net::TcpStream;
+use mio::unix::pipe::{Receiver, Sender};
+use mio::{Interest, Registry, Token};
+use uuid::Uuid;
+
+use super::{
+    CacheCapture, RawResponse, Request, Server, ServerError, ServerState, Session, Transfer,
+    UpstreamTarget,
+};
+use crate::Config;
 
 // -------------------------------------------------------------------------------------
 // CGI
 // -------------------------------------------------------------------------------------
-// const FILETYPE: &str = ".rb";
+// Redirections locales successives avant 508 Loop Detected
+const CGI_MAX_REDIRECTS: u8 = 5;
+
+pub fn default_cgi_timeout() -> u64 {
+    30
+}
+
+/// Script CGI désigné par une URL: "/cgi/test.rb/extra?x=1" -> script "/cgi/test.rb",
+/// PATH_INFO "/extra".
+#[derive(Debug, Clone)]
+pub struct CgiScript {
+    pub path: PathBuf,
+    pub interpreter: Option<String>, // None: fichier exécutable lancé directement (cgi-bin)
+    pub script_name: String,
+    pub path_info: String,
+    pub timeout: u64, // secondes, 0 = pas de limite
+    pub limits: CgiLimits,
+}
+
+/// Interpréteurs propres à `location` et ses sous-dossiers, prioritaires sur ceux du serveur.
+/// `timeout` et `limits` remplacent ceux du serveur pour ces scripts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CgiLocation {
+    pub location: String,
+    pub handlers: HashMap<String, String>,
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub limits: Option<CgiLimits>,
+}
+
+/// Limites système (setrlimit) appliquées au processus CGI avant exec.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct CgiLimits {
+    pub cpu: Option<u64>,        // secondes de temps processeur
+    pub memory: Option<u64>,     // kb d'espace d'adressage
+    pub open_files: Option<u64>, // descripteurs ouverts
+}
+
+/// Application servant `location` et ses sous-dossiers via FastCGI, SCGI ou uwsgi.
+/// `pass`: "127.0.0.1:9000" ou "unix:/run/php/php-fpm.sock".
+/// Avec `extensions`, seuls les fichiers correspondants sont transmis (SCRIPT_FILENAME);
+/// sinon toute la location est l'application et le reste de l'URL devient PATH_INFO.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GatewayLocation {
+    pub location: String,
+    pub pass: String,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    // `pass` résolue au chargement de la configuration
+    #[serde(skip)]
+    pub target: UpstreamTarget,
+}
+
+impl CgiLimits {
+    /// Appelé dans le processus fils entre fork et exec: uniquement des appels système.
+    fn apply(&self) -> io::Result<()> {
+        let set = |resource, value: u64| {
+            let limit = libc::rlimit {
+                rlim_cur: value as libc::rlim_t,
+                rlim_max: value as libc::rlim_t,
+            };
+            match unsafe { libc::setrlimit(resource, &limit) } {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            }
+        };
+        if let Some(cpu) = self.cpu {
+            set(libc::RLIMIT_CPU, cpu)?;
+        }
+        if let Some(memory) = self.memory {
+            set(libc::RLIMIT_AS, memory * 1024)?;
+        }
+        if let Some(open_files) = self.open_files {
+            set(libc::RLIMIT_NOFILE, open_files)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn default_cgi_handlers() -> HashMap<String, String> {
+    HashMap::from([(".rb".to_string(), "ruby".to_string())])
+}
+
+/// Sortie d'un script CGI interprétée (RFC 3875, section 6).
+#[derive(Debug)]
+pub enum CgiOutput {
+    /// Document ou redirection client, à renvoyer tel quel
+    Response(RawResponse),
+    /// "Location: /chemin" seul: la requête est rejouée en GET sur ce chemin
+    LocalRedirect(String),
+}
+
 pub struct CGI;
 
 impl CGI {
-    // pub fn new() -> Self {
-    //     Self {
-    //         file_type: FILETYPE.to_string(),
-    //     }
-    // }
+    /// Variables d'environnement CGI/1.1 (RFC 3875) de la requête.
+    pub fn environment(
+        server: &Server,
+        request: &Request,
+        script: &CgiScript,
+        remote_addr: &str,
+    ) -> HashMap<String, String> {
+        let mut env = HashMap::new();
+        let mut set = |key: &str, value: &str| {
+            env.insert(key.to_string(), value.to_string());
+        };
+
+        let query = request.location.split_once('?').map_or("", |(_, q)| q);
+        let (remote_host, remote_port) = remote_addr.rsplit_once(':').unwrap_or((remote_addr, ""));
+        let document_root = Path::new(&server.root_directory)
+            .canonicalize()
+            .unwrap_or_else(|_| PathBuf::from(&server.root_directory));
+
+        set("GATEWAY_INTERFACE", "CGI/1.1");
+        set("SERVER_SOFTWARE", "localhost");
+        set("SERVER_PROTOCOL", "HTTP/1.1");
+        set("SERVER_NAME", &server.hostname);
+        set("SERVER_PORT", &request.port.to_string());
+        set("REQUEST_METHOD", &request.method);
+        set("REQUEST_URI", &request.location);
+        set("QUERY_STRING", query);
+        set("SCRIPT_NAME", &script.script_name);
+        let script_filename = script
+            .path
+            .canonicalize()
+            .unwrap_or_else(|_| script.path.clone());
+        set("SCRIPT_FILENAME", &script_filename.to_string_lossy());
+        set("DOCUMENT_ROOT", &document_root.to_string_lossy());
+        set("PATH_INFO", &script.path_info);
+        if !script.path_info.is_empty() {
+            let translated = document_root.join(script.path_info.trim_start_matches('/'));
+            set("PATH_TRANSLATED", &translated.to_string_lossy());
+        }
+        set("REMOTE_ADDR", remote_host);
+        set("REMOTE_PORT", remote_port);
+        // Requis par php-cgi (cgi.force_redirect)
+        set("REDIRECT_STATUS", "200");
+
+        let body_length = request.raw_body().len();
+        if body_length > 0 || request.content_length.is_some() {
+            set("CONTENT_LENGTH", &body_length.to_string());
+        }
+        if let Some(content_type) = request.header("Content-Type") {
+            set("CONTENT_TYPE", content_type);
+        }
+
+        // Données de la session: "user" -> SESSION_USER
+        for (key, value) in &request.session {
+            let key: String = key
+                .chars()
+                .map(|c| match c.is_ascii_alphanumeric() {
+                    true => c.to_ascii_uppercase(),
+                    false => '_',
+                })
+                .collect();
+            set(&format!("SESSION_{}", key), value);
+        }
 
-    pub fn execute_file(filename: String) -> String {
-        let mut input = Command::new("ruby");
+        // En-têtes HTTP: "User-Agent" -> HTTP_USER_AGENT
+        // "Proxy" deviendrait HTTP_PROXY, lu comme proxy sortant par de nombreux clients HTTP
+        // ("httpoxy"): il n'est jamais transmis
+        for (name, value) in &request.headers {
+            let name = name.to_uppercase().replace('-', "_");
+            if name == "CONTENT_TYPE" || name == "CONTENT_LENGTH" || name == "PROXY" {
+                continue;
+            }
+            set(&format!("HTTP_{}", name), value);
+        }
 
-        input.arg(filename);
-        let res = input.output();
+        env
+    }
 
-        if res.is_ok() {
-            let check_txt = String::from_utf8(res.unwrap().stdout);
-            if check_txt.is_ok() {
-                check_txt.unwrap()
-            } else {
-                String::new()
+    /// Lance le script avec son interpréteur depuis le dossier du script, dans son propre
+    /// groupe de processus et sous ses limites système.
+    /// stdin, stdout et stderr restent ouverts: ils sont passés en non bloquant par `CgiTransfer`.
+    pub fn spawn(script: &CgiScript, env: &HashMap<String, String>) -> io::Result<Child> {
+        let path = script.path.canonicalize()?;
+        let mut command = match &script.interpreter {
+            Some(interpreter) => {
+                let mut command = Command::new(interpreter);
+                command.arg(&path);
+                command
             }
-        } else {
-            String::new()
+            None => Command::new(&path),
+        };
+        command
+            .env_clear()
+            .env("PATH", std::env::var("PATH").unwrap_or_default())
+            .envs(env)
+            .current_dir(path.parent().unwrap_or(Path::new("/")))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+
+        let limits = script.limits;
+        // SAFETY: `apply` n'appelle que setrlimit, utilisable entre fork et exec
+        unsafe {
+            command.pre_exec(move || limits.apply());
         }
+        command.spawn()
     }
 }
+
+impl CgiOutput {
+    /// Sépare les en-têtes CGI du corps et construit la réponse HTTP.
+    /// Renvoie une erreur si les en-têtes sont absents ou mal formés (502 Bad Gateway).
+    /// `output` peut ne contenir que le début du corps (sortie encore en cours).
+    pub fn parse(output: &[u8]) -> Result<Self, String> {
+        let Some((header_end, body_start)) = Self::header_end(output) else {
+            return Err("missing header section".to_string());
+        };
+        let head = std::str::from_utf8(&output[..header_end])
+            .map_err(|_| "headers are not valid UTF-8".to_string())?;
+        let body = output[body_start..].to_vec();
+
+        let mut status = None;
+        let mut location = None;
+        let mut content_type = None;
+        let mut headers = vec![];
+        let mut lines = head.lines().peekable();
+        // Ligne de statut HTTP (applications uwsgi, scripts nph): équivaut à "Status:"
+        if let Some(line) = lines.next_if(|line| line.starts_with("HTTP/")) {
+            let value = line.split_once(' ').map_or("", |(_, status)| status);
+            status = Some(Self::status_code(value)?);
+        }
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(format!("malformed header line: {:?}", line));
+            };
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c.is_control()) {
+                return Err(format!("malformed header name: {:?}", name));
+            }
+            let value = value.trim();
+            match name.to_ascii_lowercase().as_str() {
+                "status" => status = Some(Self::status_code(value)?),
+                "location" => location = Some(value.to_string()),
+                "content-type" => content_type = Some(value.to_string()),
+                // Gérés par le serveur
+                "content-length" | "connection" | "transfer-encoding" => {}
+                _ => headers.push((name.to_string(), value.to_string())),
+            }
+        }
+
+        if let Some(location) = &location {
+            // Redirection locale: Location seule, sans statut ni corps
+            if location.starts_with('/')
+                && status.is_none()
+                && content_type.is_none()
+                && headers.is_empty()
+            {
+                return Ok(CgiOutput::LocalRedirect(location.clone()));
+            }
+        } else if content_type.is_none() && status.is_none() {
+            // Le corps peut ne pas être encore arrivé: Content-Type est exigé dès les en-têtes
+            return Err("missing Content-Type header".to_string());
+        }
+
+        let status = status.unwrap_or(match location {
+            Some(_) => 302,
+            None => 200,
+        });
+        let mut response = RawResponse::status(status);
+        response.headers = headers;
+        if let Some(location) = location {
+            response = response.header("Location", &location);
+        }
+        response = match content_type {
+            Some(content_type) => response.body(&content_type, body),
+            None => response,
+        };
+        Ok(CgiOutput::Response(response))
+    }
+
+    /// Code de "Status: 404 Not Found" ou "HTTP/1.1 404 Not Found".
+    fn status_code(value: &str) -> Result<u16, String> {
+        value
+            .split_whitespace()
+            .next()
+            .and_then(|code| code.parse::<u16>().ok())
+            .filter(|code| (100..600).contains(code))
+            .ok_or(format!("invalid status: {:?}", value))
+    }
+
+    /// Fin des en-têtes: première ligne vide ("\n\n" ou "\r\n\r\n").
+    /// Renvoie la position de la ligne vide et celle du début du corps.
+    pub fn header_end(output: &[u8]) -> Option<(usize, usize)> {
+        let mut start = 0;
+        for (i, _) in output.iter().enumerate().filter(|(_, b)| **b == b'\n') {
+            let line = &output[start..i];
+            if line.is_empty() || line == b"\r" {
+                return Some((start, i + 1));
+            }
+            start = i + 1;
+        }
+        None
+    }
+}
+
+impl Server {
+    /// Lance un script CGI; sa sortie est relayée au client par le Router
+    /// au fil des événements sur les pipes du processus.
+    pub fn handle_cgi(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        script: &CgiScript,
+        cookie: &String,
+        config: &Config,
+    ) -> Option<Transfer> {
+        match self.cgi_transfer(stream, request, script, cookie) {
+            Ok(transfer) => Some(transfer),
+            Err(e) => {
+                Self::error_log(
+                    request,
+                    config,
+                    "handle_cgi",
+                    file!(),
+                    line!(),
+                    ServerError::IOError(&e),
+                );
+                self.send_raw_response(stream, request, config, cookie, RawResponse::status(500))
+            }
+        }
+    }
+
+    /// Lance le script et renvoie le transfert qui relaiera sa sortie.
+    pub fn cgi_transfer(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        script: &CgiScript,
+        cookie: &str,
+    ) -> io::Result<Transfer> {
+        let remote_addr = stream
+            .peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+        let env = CGI::environment(self, request, script, &remote_addr);
+        let child = CGI::spawn(script, &env)?;
+        let transfer = CgiTransfer::new(child, self.clone(), request.clone(), script, cookie)?;
+        Ok(Transfer::Cgi(Box::new(transfer)))
+    }
+
+    /// Termine une réponse CGI ou FastCGI: garde la réponse dans le cache de sa location,
+    /// journalise ses erreurs et l'accès, ou rejoue la requête en cas de redirection locale.
+    pub fn finish_cgi(
+        &self,
+        stream: &mut TcpStream,
+        cgi: &mut CgiResponse,
+        config: &Config,
+        state: &mut ServerState,
+    ) -> Option<Transfer> {
+        cgi.log_errors(config);
+        if let Some(capture) = cgi.capture.take() {
+            state.response_cache.store(capture, &cgi.request);
+        }
+        if let Some(new_id) = cgi.regenerate.take() {
+            // Les données suivent la session sous son nouvel identifiant
+            let old_id = std::mem::replace(&mut cgi.request.id_session, new_id.clone());
+            let data = state.sessions.load(&old_id).unwrap_or_default();
+            let moved = state.sessions.save(&new_id, &data);
+            if let Err(e) = moved.and_then(|_| state.sessions.remove(&old_id)) {
+                Self::error_log(
+                    &cgi.request,
+                    config,
+                    "finish_cgi",
+                    file!(),
+                    line!(),
+                    ServerError::IOError(&e),
+                );
+            }
+            state.regenerated_sessions.push((old_id, new_id));
+        }
+        for (key, value) in cgi.session_updates.drain(..) {
+            let id = &cgi.request.id_session;
+            if let Err(e) = state.sessions.set(id, &key, &value) {
+                Self::error_log(
+                    &cgi.request,
+                    config,
+                    "finish_cgi",
+                    file!(),
+                    line!(),
+                    ServerError::IOError(&e),
+                );
+            }
+        }
+
+        let request = &cgi.request;
+        match cgi.redirect.take() {
+            Some(location) if request.redirects < CGI_MAX_REDIRECTS => {
+                // Nouvelle requête GET interne vers `location`
+                let mut redirected = request.clone();
+                let (path, query) = Request::split_location(&location);
+                redirected.method = "GET".to_string();
+                redirected.location = location;
+                redirected.path = path;
+                redirected.query = query;
+                redirected.body.clear();
+                redirected.body_byte.clear();
+                redirected.content_length = None;
+                redirected.redirects += 1;
+                self.handle_request(stream, redirected, cgi.cookie.clone(), config, state)
+            }
+            Some(_) => self.send_raw_response(
+                stream,
+                request,
+                config,
+                &cgi.cookie,
+                RawResponse::status(508),
+            ),
+            None => {
+                self.access_log(request, config, cgi.status, &cgi.cookie);
+                None
+            }
+        }
+    }
+
+    /// Cherche un script CGI en tête du chemin de l'URL; le reste devient PATH_INFO.
+    pub fn cgi_script(&self, url_path: &str) -> Option<CgiScript> {
+        let segments: Vec<&str> = url_path.split('/').filter(|s| !s.is_empty()).collect();
+        for i in 1..=segments.len() {
+            let script_name = format!("/{}", segments[..i].join("/"));
+            let path = self.resolve_path("/", &script_name)?;
+            if path.is_dir() {
+                continue;
+            }
+            if !path.is_file() {
+                return None;
+            }
+
+            let interpreter = match self.cgi_interpreter(&script_name, &path) {
+                Some(interpreter) => Some(interpreter),
+                None if self.in_cgi_bin(&script_name) && is_executable(&path) => None,
+                None => return None,
+            };
+            let path_info = match segments[i..].is_empty() {
+                true => String::new(),
+                false => format!("/{}", segments[i..].join("/")),
+            };
+            let location = self.cgi_location(&script_name);
+            return Some(CgiScript {
+                path,
+                interpreter,
+                timeout: location
+                    .and_then(|cgi| cgi.timeout)
+                    .unwrap_or(self.cgi_timeout),
+                limits: location
+                    .and_then(|cgi| cgi.limits)
+                    .unwrap_or(self.cgi_limits),
+                script_name,
+                path_info,
+            });
+        }
+        None
+    }
+
+    /// Interpréteur associé à l'extension du script: règle de la location la plus précise,
+    /// sinon table du serveur.
+    fn cgi_interpreter(&self, script_name: &str, path: &Path) -> Option<String> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        let lookup = |handlers: &HashMap<String, String>| {
+            handlers
+                .iter()
+                .find(|(ext, _)| ext.trim_start_matches('.').to_lowercase() == extension)
+                .map(|(_, interpreter)| interpreter.clone())
+        };
+
+        self.cgi_location(script_name)
+            .and_then(|cgi| lookup(&cgi.handlers))
+            .or_else(|| lookup(&self.cgi_handlers))
+    }
+
+    /// Application de `locations` servant `url_path` (règle la plus précise)
+    /// et script correspondant.
+    pub fn gateway_script(
+        &self,
+        locations: &[GatewayLocation],
+        url_path: &str,
+    ) -> Option<(CgiScript, GatewayLocation)> {
+        let location = locations
+            .iter()
+            .filter(|gateway| is_under(url_path, &gateway.location))
+            .max_by_key(|gateway| gateway.location.trim_end_matches('/').len())?;
+
+        let segments: Vec<&str> = url_path.split('/').filter(|s| !s.is_empty()).collect();
+        let script_name = match location.extensions.is_empty() {
+            // Toute la location est l'application
+            true => location.location.trim_end_matches('/').to_string(),
+            // Premier segment désignant un fichier d'une extension transmise
+            false => (1..=segments.len())
+                .map(|i| format!("/{}", segments[..i].join("/")))
+                .find(|script_name| {
+                    let extension = script_name.rsplit_once('.').map_or("", |(_, ext)| ext);
+                    location
+                        .extensions
+                        .iter()
+                        .any(|ext| ext.trim_start_matches('.').eq_ignore_ascii_case(extension))
+                })?,
+        };
+        let path_info = url_path
+            .strip_prefix(&script_name)
+            .unwrap_or_default()
+            .to_string();
+        let path = self.resolve_path("/", &script_name).unwrap_or_default();
+
+        Some((
+            CgiScript {
+                path,
+                interpreter: None,
+                script_name,
+                path_info,
+                timeout: 0,
+                limits: CgiLimits::default(),
+            },
+            location.clone(),
+        ))
+    }
+
+    /// Règle CGI la plus précise contenant `script_name`.
+    fn cgi_location(&self, script_name: &str) -> Option<&CgiLocation> {
+        self.cgi_locations
+            .iter()
+            .filter(|cgi| is_under(script_name, &cgi.location))
+            .max_by_key(|cgi| cgi.location.trim_end_matches('/').len())
+    }
+
+    fn in_cgi_bin(&self, script_name: &str) -> bool {
+        !self.cgi_bin.is_empty() && is_under(script_name, &self.cgi_bin)
+    }
+
+    /// Vrai si un fichier placé à `url_path` serait exécuté (script CGI, application FastCGI,
+    /// SCGI ou uwsgi) plutôt que servi tel quel.
+    pub fn is_script_path(&self, url_path: &str) -> bool {
+        self.in_cgi_bin(url_path)
+            || self.cgi_interpreter(url_path, Path::new(url_path)).is_some()
+            || [
+                &self.fastcgi_locations,
+                &self.scgi_locations,
+                &self.uwsgi_locations,
+            ]
+            .iter()
+            .any(|locations| self.gateway_script(locations, url_path).is_some())
+    }
+}
+// -------------------------------------------------------------------------------------
+
 // -------------------------------------------------------------------------------------
+// CGI RESPONSE
+// -------------------------------------------------------------------------------------
+// Taille maximale de la section d'en-têtes avant 502 Bad Gateway
+const CGI_HEADER_MAX: usize = 64 * 1024;
+
+/// Sortie d'un script CGI ou d'une application FastCGI relayée au client au fur et à
+/// mesure (`Transfer-Encoding: chunked`), une fois la section d'en-têtes interprétée.
+#[derive(Debug)]
+pub struct CgiResponse {
+    // Sortie reçue tant que la section d'en-têtes n'est pas complète
+    output: Vec<u8>,
+    // Octets prêts à partir sur le socket (déjà encodés en chunked)
+    pending: Vec<u8>,
+    headers_sent: bool,
+    // Réponse sans corps (HEAD, 204, 304): rien n'est envoyé après les en-têtes
+    bodiless: bool,
+    pub finished: bool,
+    // Échec après le début de la réponse: seule la fermeture le signale au client
+    aborted: bool,
+    script: PathBuf,
+    pub server: Server,
+    pub request: Request,
+    pub cookie: String,
+    pub status: u16,
+    pub redirect: Option<String>,
+    pub errors: Vec<String>,
+    // Copie de la réponse pour le cache de la location
+    pub capture: Option<CacheCapture>,
+    // Données de session posées par le script ("X-Session: clé=valeur")
+    pub session_updates: Vec<(String, String)>,
+    // Nouvel identifiant de session demandé par "X-Session-Regenerate" (connexion...)
+    pub regenerate: Option<String>,
+    // Réponse d'un serveur HTTP amont: ses en-têtes X-Session sont retirés sans effet
+    pub proxied: bool,
+}
+
+impl CgiResponse {
+    pub fn new(server: Server, request: Request, script: &Path, cookie: &str) -> Self {
+        Self {
+            output: vec![],
+            pending: vec![],
+            headers_sent: false,
+            bodiless: false,
+            finished: false,
+            aborted: false,
+            script: script.to_path_buf(),
+            server,
+            request,
+            cookie: cookie.to_string(),
+            status: 200,
+            redirect: None,
+            errors: vec![],
+            capture: None,
+            session_updates: vec![],
+            regenerate: None,
+            proxied: false,
+        }
+    }
+
+    /// Écrit ce qui est prêt sans bloquer. Renvoie `Ok(false)` si le client n'accepte
+    /// plus de données pour l'instant.
+    pub fn flush<W: Write>(&mut self, stream: &mut W) -> io::Result<bool> {
+        while !self.pending.is_empty() {
+            match stream.write(&self.pending) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if self.aborted {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "CGI response aborted",
+            ));
+        }
+        Ok(true)
+    }
+
+    /// Ajoute un morceau de la sortie du script.
+    pub fn feed(&mut self, data: &[u8]) {
+        if self.finished || self.aborted {
+            return;
+        }
+        if self.headers_sent {
+            self.chunk(data);
+            return;
+        }
+        self.output.extend_from_slice(data);
+        if CgiOutput::header_end(&self.output).is_some() {
+            self.send_headers();
+        } else if self.output.len() > CGI_HEADER_MAX {
+            self.fail(502, "header section too large");
+        }
+    }
+
+    /// Fin de la sortie du script.
+    pub fn end(&mut self) {
+        if self.finished || self.aborted {
+            return;
+        }
+        match self.headers_sent {
+            true if !self.bodiless => self.pending.extend_from_slice(b"0\r\n\r\n"),
+            true => {}
+            false => self.send_headers(),
+        }
+        if let Some(capture) = &mut self.capture {
+            capture.complete = self.headers_sent;
+        }
+        self.finished = true;
+    }
+
+    /// Échec du script (sortie invalide, délai dépassé...): réponse d'erreur `status`,
+    /// ou fermeture de la connexion si la réponse a déjà commencé.
+    pub fn fail(&mut self, status: u16, error: &str) {
+        self.push_error(error);
+        if self.finished || self.aborted {
+            return;
+        }
+        if self.headers_sent {
+            self.aborted = true;
+            return;
+        }
+        self.status = status;
+        self.pending = RawResponse::status(status).head(&self.cookie).into_bytes();
+        self.redirect = None;
+        self.finished = true;
+    }
+
+    /// Garde une erreur (ou une ligne de stderr) pour le journal, avec le script et la requête.
+    pub fn push_error(&mut self, error: &str) {
+        let error = error.trim_end();
+        if !error.is_empty() {
+            self.errors.push(format!("{}: {}", self.tag(), error));
+        }
+    }
+
+    /// Écrit les erreurs du script (stderr, sortie invalide, délai) dans le journal d'erreurs.
+    pub fn log_errors(&mut self, config: &Config) {
+        for error in self.errors.drain(..) {
+            let e = io::Error::other(error);
+            Server::error_log(
+                &self.request,
+                config,
+                "cgi",
+                file!(),
+                line!(),
+                ServerError::IOError(&e),
+            );
+        }
+    }
+
+    /// Script et requête à l'origine d'une erreur: "src/www/cgi/test.rb (GET /cgi/test.rb?x=1)".
+    fn tag(&self) -> String {
+        format!(
+            "{} ({} {})",
+            self.script.display(),
+            self.request.method,
+            self.request.location
+        )
+    }
+
+    /// Interprète la section d'en-têtes et prépare l'envoi de la réponse.
+    fn send_headers(&mut self) {
+        match CgiOutput::parse(&self.output) {
+            Ok(CgiOutput::Response(mut response)) => {
+                // "X-Session: clé=valeur" et "X-Session-Regenerate" modifient la session au
+                // lieu d'être envoyés au client; un serveur amont n'y a pas droit
+                let (session_headers, headers): (Vec<_>, Vec<_>) =
+                    std::mem::take(&mut response.headers)
+                        .into_iter()
+                        .partition(|(name, _)| {
+                            name.eq_ignore_ascii_case("X-Session")
+                                || name.eq_ignore_ascii_case("X-Session-Regenerate")
+                        });
+                response.headers = headers;
+                let session_headers = match self.proxied {
+                    true => vec![],
+                    false => session_headers,
+                };
+                for (name, value) in session_headers {
+                    if name.eq_ignore_ascii_case("X-Session-Regenerate") {
+                        self.regenerate_session();
+                    } else if let Some((key, value)) = value.split_once('=') {
+                        if !key.trim().is_empty() {
+                            self.session_updates
+                                .push((key.trim().to_string(), value.trim().to_string()));
+                        }
+                    }
+                }
+                if !self.session_updates.is_empty() || self.regenerate.is_some() {
+                    // Réponse propre à une session: pas de cache
+                    self.capture = None;
+                }
+                let no_content = matches!(response.status, 204 | 304);
+                self.bodiless = no_content || self.request.method == "HEAD";
+                if let Some(capture) = &mut self.capture {
+                    capture.status = response.status;
+                    capture.headers = response.headers.clone();
+                    response = response.header("X-Cache", "MISS");
+                }
+                let mut response = match no_content {
+                    true => response,
+                    false => response.header("Transfer-Encoding", "chunked"),
+                };
+                let body = std::mem::take(&mut response.body);
+                self.status = response.status;
+                self.pending = response.head(&self.cookie).into_bytes();
+                self.chunk(&body);
+                self.headers_sent = true;
+            }
+            Ok(CgiOutput::LocalRedirect(location)) => {
+                self.redirect = Some(location);
+                self.finished = true;
+            }
+            Err(e) => self.fail(502, &e),
+        }
+        self.output.clear();
+    }
+
+    /// Nouvel identifiant pour la session (contre la fixation de session): le cookie de la
+    /// réponse le porte, le Router l'applique à la fin de la réponse.
+    fn regenerate_session(&mut self) {
+        let id = Uuid::new_v4().to_string();
+        let cookie = &self.server.session_cookie;
+        let lifetime = cookie.lifetime as i64 * 1000;
+        self.cookie = Session::make_cookie(cookie, &cookie.sign(&id), lifetime);
+        self.regenerate = Some(id);
+    }
+
+    fn chunk(&mut self, data: &[u8]) {
+        if let Some(capture) = &mut self.capture {
+            capture.append(data);
+        }
+        if !data.is_empty() && !self.bodiless {
+            self.pending
+                .extend_from_slice(format!("{:X}\r\n", data.len()).as_bytes());
+            self.pending.extend_from_slice(data);
+            self.pending.extend_from_slice(b"\r\n");
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
+
+// -------------------------------------------------------------------------------------
+// CGI TRANSFER
+// -------------------------------------------------------------------------------------
+// Taille maximale lue sur stdout à chaque tour
+const CGI_READ_SIZE: usize = 64 * 1024;
+
+/// Processus CGI en cours: le corps de la requête est écrit sur stdin et la sortie
+/// relayée au client au fur et à mesure, sans bloquer.
+#[derive(Debug)]
+pub struct CgiTransfer {
+    child: Child,
+    stdin: Option<Sender>,
+    stdout: Option<Receiver>,
+    stderr: Option<Receiver>,
+    // Ligne de stderr en cours de lecture
+    stderr_line: Vec<u8>,
+    // Corps de la requête et position déjà écrite sur stdin
+    input: Vec<u8>,
+    written: usize,
+    timeout: u64,
+    deadline: Option<Instant>,
+    timed_out: bool,
+    pub response: CgiResponse,
+}
+
+impl CgiTransfer {
+    pub fn new(
+        mut child: Child,
+        server: Server,
+        request: Request,
+        script: &CgiScript,
+        cookie: &str,
+    ) -> io::Result<Self> {
+        let stdin = child.stdin.take().map(Sender::from);
+        let stdout = child.stdout.take().map(Receiver::from);
+        let stderr = child.stderr.take().map(Receiver::from);
+        if let Some(stdin) = &stdin {
+            stdin.set_nonblocking(true)?;
+        }
+        for pipe in [&stdout, &stderr].into_iter().flatten() {
+            pipe.set_nonblocking(true)?;
+        }
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            stderr_line: vec![],
+            input: request.raw_body().to_vec(),
+            written: 0,
+            timeout: script.timeout,
+            deadline: Some(script.timeout)
+                .filter(|timeout| *timeout > 0)
+                .map(|timeout| Instant::now() + Duration::from_secs(timeout)),
+            timed_out: false,
+            response: CgiResponse::new(server, request, &script.path, cookie),
+        })
+    }
+
+    /// Enregistre stdout, stderr (lecture) et stdin (écriture) dans le Poll du Router,
+    /// avec les tokens fournis par `next_token`. Renvoie les tokens utilisés.
+    pub fn register(
+        &mut self,
+        registry: &Registry,
+        mut next_token: impl FnMut() -> Token,
+    ) -> io::Result<Vec<Token>> {
+        let mut tokens = vec![];
+        for pipe in [&mut self.stdout, &mut self.stderr].into_iter().flatten() {
+            let token = next_token();
+            registry.register(pipe, token, Interest::READABLE)?;
+            tokens.push(token);
+        }
+        if let Some(stdin) = &mut self.stdin {
+            let token = next_token();
+            registry.register(stdin, token, Interest::WRITABLE)?;
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    /// Instant où le script sera arrêté, `None` sans limite ou une fois arrêté.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline.filter(|_| !self.timed_out)
+    }
+
+    /// Délai dépassé: le groupe de processus est tué et le client reçoit 504 Gateway
+    /// Timeout, ou voit sa connexion fermée si la réponse avait déjà commencé.
+    pub fn expire(&mut self) {
+        self.timed_out = true;
+        self.kill_group();
+        self.read_errors();
+        self.stdin = None;
+        self.stdout = None;
+        self.stderr = None;
+        let error = format!("timed out after {}s", self.timeout);
+        self.response.fail(504, &error);
+    }
+
+    /// Fait avancer stdin, stdout et l'envoi au client autant que possible sans bloquer.
+    /// Renvoie `Ok(true)` lorsque la réponse est terminée (ou remplacée par une redirection).
+    pub fn advance<W: Write>(&mut self, stream: &mut W) -> io::Result<bool> {
+        self.read_errors();
+        self.write_input();
+        loop {
+            if !self.response.flush(stream)? {
+                return Ok(false);
+            }
+            if self.response.finished {
+                return Ok(true);
+            }
+
+            self.write_input();
+            if !self.read_output()? {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Écrit la suite du corps sur stdin, puis le ferme pour signaler la fin au script.
+    fn write_input(&mut self) {
+        let Some(stdin) = &mut self.stdin else {
+            return;
+        };
+        while self.written < self.input.len() {
+            match stdin.write(&self.input[self.written..]) {
+                Ok(n) => self.written += n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // Le script n'a pas tout lu (BrokenPipe): le reste est abandonné
+                Err(_) => break,
+            }
+        }
+        self.stdin = None;
+    }
+
+    /// Transmet chaque ligne complète de stderr au journal d'erreurs.
+    fn read_errors(&mut self) {
+        let Some(stderr) = &mut self.stderr else {
+            return;
+        };
+        let mut buffer = [0; 4096];
+        let closed = loop {
+            match stderr.read(&mut buffer) {
+                Ok(0) => break true,
+                Ok(read) => self.stderr_line.extend_from_slice(&buffer[..read]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break false,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => break true,
+            }
+        };
+
+        while let Some(end) = self.stderr_line.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.stderr_line.drain(..=end).collect();
+            self.response.push_error(&String::from_utf8_lossy(&line));
+        }
+        if closed {
+            let line = std::mem::take(&mut self.stderr_line);
+            self.response.push_error(&String::from_utf8_lossy(&line));
+            self.stderr = None;
+        }
+    }
+
+    /// Tue le script et les processus qu'il a lancés (même groupe).
+    fn kill_group(&mut self) {
+        // SAFETY: simple appel système; le pid est celui du fils, chef de son groupe
+        unsafe {
+            libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL);
+        }
+    }
+
+    /// Lit un morceau de stdout. Renvoie `Ok(false)` s'il n'y a rien à lire pour l'instant.
+    fn read_output(&mut self) -> io::Result<bool> {
+        let Some(stdout) = &mut self.stdout else {
+            return Ok(false);
+        };
+        let mut buffer = vec![0; CGI_READ_SIZE];
+        match stdout.read(&mut buffer) {
+            Ok(0) => {
+                // Fin de la sortie du script
+                self.stdout = None;
+                self.response.end();
+            }
+            Ok(read) => self.response.feed(&buffer[..read]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+        Ok(true)
+    }
+}
+
+impl Drop for CgiTransfer {
+    /// Un script encore actif une fois la réponse terminée (ou le client parti) est arrêté.
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            self.kill_group();
+        }
+        let _ = self.child.wait();
+    }
+}
+// -------------------------------------------------------------------------------------
+
+pub fn is_under(path: &str, location: &str) -> bool {
+    let location = location.trim_end_matches('/');
+    path == location || path.starts_with(&format!("{}/", location))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Serveur minimal sur src/www pour les tests.
+    fn test_server() -> Server {
+        Server::new(
+            "127.0.0.1".to_string(),
+            "localhost".to_string(),
+            vec![8080],
+            "src/www".to_string(),
+            String::new(),
+            String::new(),
+            0,
+            vec![],
+            false,
+            vec![],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn test_cgi_environment() {
+        let server = test_server();
+        let mut request = Request::default();
+        request.method = "POST".to_string();
+        request.location = "/cgi/test.rb/extra?a=1".to_string();
+        request.port = 8080;
+        request.body_byte = b"POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\nx=1".to_vec();
+        request.content_length = Some(3);
+        request
+            .headers
+            .insert("Content-Type".to_string(), "text/plain".to_string());
+        request
+            .headers
+            .insert("X-Forwarded-For".to_string(), "10.0.0.1".to_string());
+        request
+            .headers
+            .insert("Proxy".to_string(), "http://evil:8080".to_string());
+        request
+            .session
+            .insert("user-name".to_string(), "alice".to_string());
+        let script = CgiScript {
+            path: PathBuf::from("src/www/cgi/test.rb"),
+            interpreter: Some("ruby".to_string()),
+            script_name: "/cgi/test.rb".to_string(),
+            path_info: "/extra".to_string(),
+            timeout: 0,
+            limits: CgiLimits::default(),
+        };
+
+        let env = CGI::environment(&server, &request, &script, "127.0.0.1:4242");
+        assert_eq!(env["QUERY_STRING"], "a=1");
+        assert_eq!(env["PATH_INFO"], "/extra");
+        assert_eq!(env["SCRIPT_NAME"], "/cgi/test.rb");
+        assert_eq!(env["CONTENT_LENGTH"], "3");
+        assert_eq!(env["CONTENT_TYPE"], "text/plain");
+        assert_eq!(env["HTTP_X_FORWARDED_FOR"], "10.0.0.1");
+        assert_eq!(env["REMOTE_ADDR"], "127.0.0.1");
+        assert_eq!(env["SESSION_USER_NAME"], "alice");
+        assert!(!env.contains_key("HTTP_CONTENT_TYPE"));
+        assert!(!env.contains_key("HTTP_PROXY"));
+    }
+
+    #[test]
+    fn test_session_updates() {
+        let server = test_server();
+        let mut response = CgiResponse::new(server, Request::default(), Path::new("test.sh"), "");
+        response.feed(
+            b"Content-Type: text/plain\r\nX-Session: user = alice\r\nx-session: cart=\r\n\
+            X-Session: bad\r\n\r\nok",
+        );
+        assert_eq!(
+            response.session_updates,
+            vec![
+                ("user".to_string(), "alice".to_string()),
+                ("cart".to_string(), String::new())
+            ]
+        );
+        let head = String::from_utf8_lossy(&response.pending).to_lowercase();
+        assert!(!head.contains("x-session"));
+        assert!(response.regenerate.is_none());
+
+        let mut response = CgiResponse::new(
+            response.server,
+            Request::default(),
+            Path::new("test.sh"),
+            "Set-Cookie: cookie_01=old\r\n",
+        );
+        response.feed(b"Content-Type: text/plain\r\nX-Session-Regenerate: 1\r\n\r\nok");
+        let id = response
+            .regenerate
+            .clone()
+            .expect("new session id expected");
+        let head = String::from_utf8_lossy(&response.pending).to_string();
+        assert!(head.contains(&format!("Set-Cookie: cookie_01={};", id)));
+        assert!(!head.contains("cookie_01=old") && !head.contains("X-Session"));
+
+        // Réponse d'un serveur amont: en-têtes retirés, session intacte
+        let mut response = CgiResponse::new(
+            response.server,
+            Request::default(),
+            Path::new("/api"),
+            "Set-Cookie: cookie_01=old\r\n",
+        );
+        response.proxied = true;
+        response.feed(
+            b"Content-Type: text/plain\r\nX-Session: user=admin\r\nX-Session-Regenerate: 1\r\n\r\nok",
+        );
+        assert!(response.session_updates.is_empty() && response.regenerate.is_none());
+        let head = String::from_utf8_lossy(&response.pending).to_string();
+        assert!(head.contains("cookie_01=old") && !head.contains("X-Session"));
+    }
+
+    #[test]
+    fn test_parse_cgi_output() {
+        let output =
+            b"Content-Type: text/html\r\nSet-Cookie: a=b\r\nStatus: 404 Not Found\r\n\r\n<p>x</p>";
+        let Ok(CgiOutput::Response(response)) = CgiOutput::parse(output) else {
+            panic!("document response expected");
+        };
+        assert_eq!(response.status, 404);
+        assert_eq!(response.body, b"<p>x</p>");
+        assert!(response
+            .headers
+            .contains(&("Set-Cookie".to_string(), "a=b".to_string())));
+        assert!(response
+            .headers
+            .contains(&("Content-Type".to_string(), "text/html".to_string())));
+
+        let Ok(CgiOutput::Response(response)) =
+            CgiOutput::parse(b"Location: http://example.com/\n\n")
+        else {
+            panic!("client redirect expected");
+        };
+        assert_eq!(response.status, 302);
+
+        assert!(matches!(
+            CgiOutput::parse(b"Location: /index.html\n\n"),
+            Ok(CgiOutput::LocalRedirect(location)) if location == "/index.html"
+        ));
+
+        assert!(CgiOutput::parse(b"Hello World\n").is_err());
+        assert!(CgiOutput::parse(b"Bad Header\n\nbody").is_err());
+        assert!(CgiOutput::parse(b"Status: abc\n\n").is_err());
+        assert!(matches!(
+            CgiOutput::parse(b"HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\n\r\nok"),
+            Ok(CgiOutput::Response(response)) if response.status == 201
+        ));
+        assert!(CgiOutput::parse(b"HTTP/1.1 OK\r\n\r\n").is_err());
+        assert!(CgiOutput::parse(b"X-Foo: bar\n\nbody without type").is_err());
+    }
+
+    #[test]
+    fn test_cgi_transfer_streams_output() {
+        let path = std::env::temp_dir().join(format!("cgi_{}.sh", std::process::id()));
+        std::fs::write(
+            &path,
+            "printf 'Content-Type: text/plain\\r\\n\\r\\n'\nprintf hello\ncat\n",
+        )
+        .unwrap();
+        let script = CgiScript {
+            path: path.clone(),
+            interpreter: Some("/bin/sh".to_string()),
+            script_name: "/test.sh".to_string(),
+            path_info: String::new(),
+            timeout: 0,
+            limits: CgiLimits::default(),
+        };
+        let mut request = Request::default();
+        request.method = "PUT".to_string();
+        request.body_byte = b" world".to_vec();
+        let server = test_server();
+
+        let child = CGI::spawn(&script, &HashMap::new()).unwrap();
+        let mut transfer = CgiTransfer::new(child, server, request, &script, "").unwrap();
+        let mut output = vec![];
+        while !transfer.advance(&mut output).unwrap() {
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(output.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(!output.contains("Content-Length"));
+        let body = &output[output.find("\r\n\r\n").unwrap() + 4..];
+        let chunks: String = body.split("\r\n").skip(1).step_by(2).collect();
+        assert_eq!(chunks, "hello world");
+        assert!(body.ends_with("0\r\n\r\n"));
+    }
+
+    fn shell_transfer(name: &str, source: &str, limits: CgiLimits) -> (CgiTransfer, PathBuf) {
+        let path = std::env::temp_dir().join(format!("cgi_{}_{}.sh", name, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let script = CgiScript {
+            path: path.clone(),
+            interpreter: Some("/bin/sh".to_string()),
+            script_name: format!("/{}.sh", name),
+            path_info: String::new(),
+            timeout: 1,
+            limits,
+        };
+        let mut request = Request::default();
+        request.method = "GET".to_string();
+        request.location = format!("/{}.sh", name);
+        let server = test_server();
+        let child = CGI::spawn(&script, &HashMap::new()).unwrap();
+        let transfer = CgiTransfer::new(child, server, request, &script, "").unwrap();
+        (transfer, path)
+    }
+
+    #[test]
+    fn test_cgi_timeout_and_stderr() {
+        let (mut transfer, path) =
+            shell_transfer("slow", "echo oops >&2\nsleep 5\n", CgiLimits::default());
+        let mut output = vec![];
+        for _ in 0..100 {
+            assert!(!transfer.advance(&mut output).unwrap());
+            if !transfer.response.errors.is_empty() {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(transfer.response.errors[0].ends_with("(GET /slow.sh): oops"));
+        assert!(transfer.deadline().is_some());
+
+        transfer.expire();
+        assert!(transfer.deadline().is_none());
+        assert!(transfer.advance(&mut output).unwrap());
+        assert!(output.starts_with(b"HTTP/1.1 504 Gateway Timeout\r\n"));
+        assert_eq!(transfer.response.status, 504);
+        assert!(transfer.response.errors[1].ends_with("timed out after 1s"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_cgi_limits() {
+        let limits = CgiLimits {
+            open_files: Some(32),
+            ..Default::default()
+        };
+        let (mut transfer, path) = shell_transfer(
+            "limits",
+            "printf 'Content-Type: text/plain\\n\\n'\nulimit -n\n",
+            limits,
+        );
+        let mut output = vec![];
+        while !transfer.advance(&mut output).unwrap() {
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert!(String::from_utf8_lossy(&output).contains("\r\n3\r\n32\n\r\n"));
+    }
+
+    #[test]
+    fn test_cgi_interpreter() {
+        let mut server = test_server();
+        server.cgi_locations = vec![CgiLocation {
+            location: "/tools/".to_string(),
+            handlers: HashMap::from([("py".to_string(), "python3".to_string())]),
+            timeout: None,
+            limits: None,
+        }];
+
+        let interpreter = |url: &str| server.cgi_interpreter(url, Path::new(url));
+        assert_eq!(interpreter("/a/test.RB"), Some("ruby".to_string()));
+        assert_eq!(interpreter("/tools/x/run.py"), Some("python3".to_string()));
+        assert_eq!(interpreter("/toolsbis/run.py"), None);
+        assert_eq!(interpreter("/a/readme"), None);
+
+        // Fichiers qu'un envoi ne doit pas créer
+        server.cgi_bin = "/cgi-bin".to_string();
+        assert!(server.is_script_path("/cgi-bin/x.sh"));
+        assert!(server.is_script_path("/uploads/x.rb"));
+        assert!(!server.is_script_path("/uploads/x.txt"));
+    }
+}
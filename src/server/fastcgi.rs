@@ -0,0 +1,706 @@
+use mio::net::TcpStream;
+use mio::{Registry, Token};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+
+use super::{
+    CgiResponse, CgiScript, GatewayLocation, Request, Server, Transfer, UpstreamSocket,
+    UpstreamTarget, CGI,
+};
+
+// -------------------------------------------------------------------------------------
+// FASTCGI
+// -------------------------------------------------------------------------------------
+// Types d'enregistrements (FastCGI 1.0, section 8)
+const FCGI_VERSION_1: u8 = 1;
+const FCGI_BEGIN_REQUEST: u8 = 1;
+const FCGI_ABORT_REQUEST: u8 = 2;
+const FCGI_END_REQUEST: u8 = 3;
+const FCGI_PARAMS: u8 = 4;
+const FCGI_STDIN: u8 = 5;
+const FCGI_STDOUT: u8 = 6;
+const FCGI_STDERR: u8 = 7;
+const FCGI_GET_VALUES: u8 = 9;
+const FCGI_GET_VALUES_RESULT: u8 = 10;
+const FCGI_RESPONDER: u16 = 1;
+const FCGI_KEEP_CONN: u8 = 1;
+// protocolStatus de FCGI_END_REQUEST
+const FCGI_REQUEST_COMPLETE: u8 = 0;
+const FCGI_CANT_MPX_CONN: u8 = 1;
+const FCGI_OVERLOADED: u8 = 2;
+// Variable de gestion: l'application accepte-t-elle plusieurs requêtes par connexion ?
+const FCGI_MPXS_CONNS: &str = "FCGI_MPXS_CONNS";
+// Longueur maximale du contenu d'un enregistrement
+const FCGI_MAX_CONTENT: usize = 65535;
+const FCGI_HEADER_LEN: usize = 8;
+
+/// Enregistrement FastCGI reçu de l'application.
+#[derive(Debug, PartialEq)]
+pub struct FastCgiRecord {
+    pub kind: u8,
+    pub id: u16,
+    pub content: Vec<u8>,
+}
+
+impl FastCgiRecord {
+    /// Encode un flux: plusieurs enregistrements si le contenu dépasse 65535 octets,
+    /// un enregistrement vide s'il est vide (fin de flux).
+    pub fn encode(kind: u8, id: u16, content: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut chunks: Vec<&[u8]> = content.chunks(FCGI_MAX_CONTENT).collect();
+        if chunks.is_empty() {
+            chunks.push(&[]);
+        }
+        for chunk in chunks {
+            // Contenu aligné sur 8 octets
+            let padding = (8 - chunk.len() % 8) % 8;
+            bytes.extend_from_slice(&[FCGI_VERSION_1, kind]);
+            bytes.extend_from_slice(&id.to_be_bytes());
+            bytes.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(&[padding as u8, 0]);
+            bytes.extend_from_slice(chunk);
+            bytes.extend(std::iter::repeat_n(0, padding));
+        }
+        bytes
+    }
+
+    /// Lit un enregistrement complet en tête de `buffer` et renvoie le nombre d'octets consommés.
+    pub fn parse(buffer: &[u8]) -> Option<(Self, usize)> {
+        if buffer.len() < FCGI_HEADER_LEN {
+            return None;
+        }
+        let id = u16::from_be_bytes([buffer[2], buffer[3]]);
+        let length = u16::from_be_bytes([buffer[4], buffer[5]]) as usize;
+        let total = FCGI_HEADER_LEN + length + buffer[6] as usize;
+        if buffer.len() < total {
+            return None;
+        }
+        let record = Self {
+            kind: buffer[1],
+            id,
+            content: buffer[FCGI_HEADER_LEN..FCGI_HEADER_LEN + length].to_vec(),
+        };
+        Some((record, total))
+    }
+}
+
+/// Paires nom-valeur de FCGI_PARAMS (longueurs sur 1 octet, ou 4 au-delà de 127).
+pub fn encode_params(params: &HashMap<String, String>) -> Vec<u8> {
+    let mut bytes = vec![];
+    let length = |bytes: &mut Vec<u8>, len: usize| match len {
+        0..=127 => bytes.push(len as u8),
+        _ => bytes.extend_from_slice(&(len as u32 | 0x8000_0000).to_be_bytes()),
+    };
+    for (name, value) in params {
+        length(&mut bytes, name.len());
+        length(&mut bytes, value.len());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(value.as_bytes());
+    }
+    bytes
+}
+
+/// Décode les paires nom-valeur d'un enregistrement (FCGI_GET_VALUES_RESULT).
+pub fn decode_params(mut bytes: &[u8]) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let length = |bytes: &mut &[u8]| -> Option<usize> {
+        match bytes.first()? {
+            len if len & 0x80 == 0 => {
+                *bytes = &bytes[1..];
+                Some(*len as usize)
+            }
+            _ if bytes.len() >= 4 => {
+                let len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                *bytes = &bytes[4..];
+                Some((len & 0x7fff_ffff) as usize)
+            }
+            _ => None,
+        }
+    };
+    while let (Some(name), Some(value)) = (length(&mut bytes), length(&mut bytes)) {
+        if bytes.len() < name + value {
+            break;
+        }
+        params.insert(
+            String::from_utf8_lossy(&bytes[..name]).to_string(),
+            String::from_utf8_lossy(&bytes[name..name + value]).to_string(),
+        );
+        bytes = &bytes[name + value..];
+    }
+    params
+}
+// -------------------------------------------------------------------------------------
+
+// -------------------------------------------------------------------------------------
+// FASTCGI TRANSFER
+// -------------------------------------------------------------------------------------
+/// Requête confiée à une application FastCGI; ses enregistrements FCGI_STDOUT sont
+/// relayés au client par le `FastCgiPool` du Router.
+#[derive(Debug)]
+pub struct FastCgiTransfer {
+    pub pass: String,
+    target: UpstreamTarget,
+    params: HashMap<String, String>,
+    body: Vec<u8>,
+    // Connexion et identifiant attribués par le pool
+    pub connection: Option<Token>,
+    pub id: u16,
+    pub response: CgiResponse,
+}
+
+impl FastCgiTransfer {
+    /// Enregistrements de la requête: BEGIN_REQUEST, PARAMS puis STDIN, chacun terminé
+    /// par un enregistrement vide.
+    fn records(&self, id: u16) -> Vec<u8> {
+        let mut begin = FCGI_RESPONDER.to_be_bytes().to_vec();
+        begin.extend_from_slice(&[FCGI_KEEP_CONN, 0, 0, 0, 0, 0]);
+
+        let mut bytes = FastCgiRecord::encode(FCGI_BEGIN_REQUEST, id, &begin);
+        let params = encode_params(&self.params);
+        if !params.is_empty() {
+            bytes.extend(FastCgiRecord::encode(FCGI_PARAMS, id, &params));
+        }
+        bytes.extend(FastCgiRecord::encode(FCGI_PARAMS, id, &[]));
+        if !self.body.is_empty() {
+            bytes.extend(FastCgiRecord::encode(FCGI_STDIN, id, &self.body));
+        }
+        bytes.extend(FastCgiRecord::encode(FCGI_STDIN, id, &[]));
+        bytes
+    }
+
+    /// Envoie au client ce que l'application a déjà produit.
+    /// Renvoie `Ok(true)` lorsque la réponse est terminée.
+    pub fn advance<W: Write>(&mut self, stream: &mut W) -> io::Result<bool> {
+        Ok(self.response.flush(stream)? && self.response.finished)
+    }
+}
+// -------------------------------------------------------------------------------------
+
+// -------------------------------------------------------------------------------------
+// FASTCGI POOL
+// -------------------------------------------------------------------------------------
+/// Connexion persistante vers une application. Elle ne porte qu'une requête à la fois,
+/// sauf si l'application a annoncé FCGI_MPXS_CONNS=1.
+#[derive(Debug)]
+struct FastCgiConnection {
+    socket: UpstreamSocket,
+    pass: String,
+    // Enregistrements en attente d'écriture et octets reçus non encore interprétés
+    outgoing: Vec<u8>,
+    incoming: Vec<u8>,
+    // requestId -> token du client
+    requests: HashMap<u16, Token>,
+    // Requêtes abandonnées dont l'application n'a pas encore envoyé FCGI_END_REQUEST
+    aborted: HashSet<u16>,
+    next_id: u16,
+}
+
+impl FastCgiConnection {
+    /// Prochain requestId libre (0 est réservé aux enregistrements de gestion).
+    fn allocate_id(&mut self) -> u16 {
+        loop {
+            self.next_id = self.next_id.checked_add(1).unwrap_or(1);
+            if !self.requests.contains_key(&self.next_id) && !self.aborted.contains(&self.next_id) {
+                return self.next_id;
+            }
+        }
+    }
+
+    /// Aucune requête en cours, abandonnées comprises.
+    fn is_idle(&self) -> bool {
+        self.requests.is_empty() && self.aborted.is_empty()
+    }
+
+    /// Écrit les enregistrements en attente sans bloquer.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.socket.write(&self.outgoing) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero)),
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                // Connexion TCP encore en cours d'établissement
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::NotConnected =>
+                {
+                    return Ok(())
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Lit tout ce qui est disponible. Renvoie `Ok(false)` si l'application a fermé la connexion.
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut buffer = [0; 64 * 1024];
+        loop {
+            match self.socket.read(&mut buffer) {
+                Ok(0) => return Ok(false),
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::NotConnected =>
+                {
+                    return Ok(true)
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Connexions du Router vers les applications FastCGI; les connexions inactives sont
+/// réutilisées par les requêtes suivantes.
+#[derive(Debug, Default)]
+pub struct FastCgiPool {
+    connections: HashMap<Token, FastCgiConnection>,
+    by_pass: HashMap<String, Vec<Token>>,
+    // Réponse de chaque application à FCGI_GET_VALUES (FCGI_MPXS_CONNS)
+    multiplexed: HashMap<String, bool>,
+}
+
+impl FastCgiPool {
+    pub fn contains(&self, token: Token) -> bool {
+        self.connections.contains_key(&token)
+    }
+
+    /// Envoie la requête de `transfer` à son application: sur une connexion inactive, sur
+    /// n'importe quelle connexion si l'application multiplexe, sinon sur une nouvelle
+    /// connexion enregistrée dans le Poll.
+    pub fn attach(
+        &mut self,
+        registry: &Registry,
+        next_token: impl FnOnce() -> Token,
+        client: Token,
+        transfer: &mut FastCgiTransfer,
+    ) -> io::Result<()> {
+        let multiplexed = self.multiplexed.get(&transfer.pass) == Some(&true);
+        let reusable = self.by_pass.get(&transfer.pass).and_then(|tokens| {
+            tokens
+                .iter()
+                .find(|token| multiplexed || self.connections[*token].is_idle())
+                .copied()
+        });
+        let token = match reusable {
+            Some(token) => token,
+            None => {
+                let mut socket = UpstreamSocket::connect(&transfer.target)?;
+                let token = next_token();
+                socket.register(registry, token)?;
+                let mut outgoing = vec![];
+                if !self.multiplexed.contains_key(&transfer.pass) {
+                    // Demander une fois à l'application si elle multiplexe les requêtes
+                    let query = HashMap::from([(FCGI_MPXS_CONNS.to_string(), String::new())]);
+                    outgoing = FastCgiRecord::encode(FCGI_GET_VALUES, 0, &encode_params(&query));
+                }
+                self.connections.insert(
+                    token,
+                    FastCgiConnection {
+                        socket,
+                        pass: transfer.pass.clone(),
+                        outgoing,
+                        incoming: vec![],
+                        requests: HashMap::new(),
+                        aborted: HashSet::new(),
+                        next_id: 0,
+                    },
+                );
+                self.by_pass
+                    .entry(transfer.pass.clone())
+                    .or_default()
+                    .push(token);
+                token
+            }
+        };
+
+        let connection = self.connections.get_mut(&token).expect("connexion FastCGI");
+        let id = connection.allocate_id();
+        connection.outgoing.extend(transfer.records(id));
+        if let Err(e) = connection.flush() {
+            // Connexion inutilisable: la prochaine requête en ouvrira une nouvelle
+            self.close(token, registry);
+            return Err(e);
+        }
+        connection.requests.insert(id, client);
+        transfer.connection = Some(token);
+        transfer.id = id;
+        Ok(())
+    }
+
+    /// Traite un événement sur la connexion `token`: envoie les enregistrements en attente
+    /// et distribue ceux reçus aux réponses des clients. Renvoie les clients concernés.
+    pub fn ready(
+        &mut self,
+        token: Token,
+        registry: &Registry,
+        transfers: &mut HashMap<Token, Transfer>,
+    ) -> Vec<Token> {
+        let Some(connection) = self.connections.get_mut(&token) else {
+            return vec![];
+        };
+        let mut clients = vec![];
+        let status = connection.flush().and_then(|_| connection.fill());
+
+        let mut consumed = 0;
+        while let Some((record, length)) = FastCgiRecord::parse(&connection.incoming[consumed..]) {
+            consumed += length;
+            if record.kind == FCGI_GET_VALUES_RESULT {
+                let values = decode_params(&record.content);
+                let multiplexed = values
+                    .get(FCGI_MPXS_CONNS)
+                    .is_some_and(|value| value == "1");
+                self.multiplexed
+                    .insert(connection.pass.clone(), multiplexed);
+                continue;
+            }
+            if connection.aborted.contains(&record.id) {
+                // Sortie d'une requête abandonnée: ignorée jusqu'à sa fin
+                if record.kind == FCGI_END_REQUEST {
+                    connection.aborted.remove(&record.id);
+                }
+                continue;
+            }
+            let Some(&client) = connection.requests.get(&record.id) else {
+                continue;
+            };
+            let Some(Transfer::FastCgi(transfer)) = transfers.get_mut(&client) else {
+                continue;
+            };
+            match record.kind {
+                FCGI_STDOUT if record.content.is_empty() => {}
+                FCGI_STDOUT => transfer.response.feed(&record.content),
+                FCGI_STDERR => {
+                    for line in String::from_utf8_lossy(&record.content).lines() {
+                        transfer.response.push_error(line);
+                    }
+                }
+                FCGI_END_REQUEST => {
+                    connection.requests.remove(&record.id);
+                    // protocolStatus après les 4 octets de appStatus
+                    match record
+                        .content
+                        .get(4)
+                        .copied()
+                        .unwrap_or(FCGI_REQUEST_COMPLETE)
+                    {
+                        FCGI_REQUEST_COMPLETE => transfer.response.end(),
+                        FCGI_OVERLOADED => transfer
+                            .response
+                            .fail(503, "FastCGI application overloaded"),
+                        FCGI_CANT_MPX_CONN => {
+                            self.multiplexed.insert(connection.pass.clone(), false);
+                            transfer
+                                .response
+                                .fail(502, "FastCGI application cannot multiplex connections");
+                        }
+                        _ => transfer
+                            .response
+                            .fail(502, "FastCGI application does not support the role"),
+                    }
+                }
+                _ => continue,
+            }
+            if !clients.contains(&client) {
+                clients.push(client);
+            }
+        }
+        connection.incoming.drain(..consumed);
+
+        // Connexion fermée ou en erreur: les requêtes en cours échouent
+        let error = match status {
+            Ok(true) => return clients,
+            Ok(false) => "connection closed by FastCGI application".to_string(),
+            Err(e) => e.to_string(),
+        };
+        let connection = self.close(token, registry).expect("connexion FastCGI");
+        for (_, client) in connection.requests {
+            if let Some(Transfer::FastCgi(transfer)) = transfers.get_mut(&client) {
+                transfer.response.fail(502, &error);
+                if !clients.contains(&client) {
+                    clients.push(client);
+                }
+            }
+        }
+        clients
+    }
+
+    fn close(&mut self, token: Token, registry: &Registry) -> Option<FastCgiConnection> {
+        let mut connection = self.connections.remove(&token)?;
+        if let Some(tokens) = self.by_pass.get_mut(&connection.pass) {
+            tokens.retain(|other| *other != token);
+        }
+        let _ = connection.socket.deregister(registry);
+        Some(connection)
+    }
+
+    /// Le client est parti avant la fin: l'application est priée d'abandonner la requête.
+    pub fn abort(&mut self, transfer: &FastCgiTransfer) {
+        let Some(connection) = transfer
+            .connection
+            .and_then(|token| self.connections.get_mut(&token))
+        else {
+            return;
+        };
+        if connection.requests.remove(&transfer.id).is_some() {
+            // La connexion reste occupée jusqu'au FCGI_END_REQUEST de la requête abandonnée
+            connection.aborted.insert(transfer.id);
+            connection
+                .outgoing
+                .extend(FastCgiRecord::encode(FCGI_ABORT_REQUEST, transfer.id, &[]));
+            let _ = connection.flush();
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
+
+impl Server {
+    /// Transmet la requête à l'application FastCGI de la location; la réponse est relayée
+    /// par le Router au fil des enregistrements reçus.
+    pub fn handle_fastcgi(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        script: &CgiScript,
+        gateway: &GatewayLocation,
+        cookie: &str,
+    ) -> Option<Transfer> {
+        let remote_addr = stream
+            .peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+        let params = CGI::environment(self, request, script, &remote_addr);
+
+        Some(Transfer::FastCgi(Box::new(FastCgiTransfer {
+            pass: gateway.pass.clone(),
+            target: gateway.target.clone(),
+            params,
+            body: request.raw_body().to_vec(),
+            connection: None,
+            id: 0,
+            response: CgiResponse::new(self.clone(), request.clone(), &script.path, cookie),
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mio::{Events, Poll};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Application FastCGI minimale: une requête à la fois par connexion (FCGI_MPXS_CONNS=0),
+    /// terminée par `protocol_status`.
+    fn responder(protocol_status: u8) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                thread::spawn(move || {
+                    let mut incoming = vec![];
+                    let mut buffer = [0; 4096];
+                    while let Ok(read @ 1..) = stream.read(&mut buffer) {
+                        incoming.extend_from_slice(&buffer[..read]);
+                        while let Some((record, length)) = FastCgiRecord::parse(&incoming) {
+                            incoming.drain(..length);
+                            let mut reply = vec![];
+                            match record.kind {
+                                FCGI_GET_VALUES => {
+                                    let values = HashMap::from([(
+                                        FCGI_MPXS_CONNS.to_string(),
+                                        "0".to_string(),
+                                    )]);
+                                    let values = encode_params(&values);
+                                    reply =
+                                        FastCgiRecord::encode(FCGI_GET_VALUES_RESULT, 0, &values);
+                                }
+                                FCGI_STDIN if record.content.is_empty() => {
+                                    if protocol_status == FCGI_REQUEST_COMPLETE {
+                                        let output = b"Content-Type: text/plain\r\n\r\nhello";
+                                        reply =
+                                            FastCgiRecord::encode(FCGI_STDOUT, record.id, output);
+                                        reply.extend(FastCgiRecord::encode(
+                                            FCGI_STDOUT,
+                                            record.id,
+                                            &[],
+                                        ));
+                                    }
+                                    let end = [0, 0, 0, 0, protocol_status, 0, 0, 0];
+                                    reply.extend(FastCgiRecord::encode(
+                                        FCGI_END_REQUEST,
+                                        record.id,
+                                        &end,
+                                    ));
+                                }
+                                _ => {}
+                            }
+                            stream.write_all(&reply).unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    fn transfer(pass: &str) -> Transfer {
+        let server = Server::new(
+            "127.0.0.1".to_string(),
+            "localhost".to_string(),
+            vec![8080],
+            "src/www".to_string(),
+            String::new(),
+            String::new(),
+            0,
+            vec![],
+            false,
+            vec![],
+            vec![],
+        );
+        let response = CgiResponse::new(server, Request::default(), Path::new("index.php"), "");
+        Transfer::FastCgi(Box::new(FastCgiTransfer {
+            pass: pass.to_string(),
+            target: UpstreamTarget::resolve(pass),
+            params: HashMap::from([("SCRIPT_NAME".to_string(), "/index.php".to_string())]),
+            body: vec![],
+            connection: None,
+            id: 0,
+            response,
+        }))
+    }
+
+    /// Envoie les requêtes `clients` à l'application et attend toutes les réponses.
+    fn run(
+        pool: &mut FastCgiPool,
+        poll: &mut Poll,
+        transfers: &mut HashMap<Token, Transfer>,
+        clients: &[usize],
+        pass: &str,
+    ) {
+        let mut next = 100 + pool.connections.len();
+        for client in clients {
+            let mut fastcgi = transfer(pass);
+            let Transfer::FastCgi(inner) = &mut fastcgi else {
+                unreachable!()
+            };
+            let allocate = || {
+                next += 1;
+                Token(next)
+            };
+            pool.attach(poll.registry(), allocate, Token(*client), inner)
+                .unwrap();
+            transfers.insert(Token(*client), fastcgi);
+        }
+        let finished = |transfers: &HashMap<Token, Transfer>| {
+            clients.iter().all(|client| {
+                matches!(&transfers[&Token(*client)], Transfer::FastCgi(t) if t.response.finished)
+            })
+        };
+        let mut events = Events::with_capacity(16);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !finished(transfers) {
+            assert!(Instant::now() < deadline, "pas de réponse FastCGI");
+            poll.poll(&mut events, Some(Duration::from_millis(100)))
+                .unwrap();
+            for event in events.iter() {
+                pool.ready(event.token(), poll.registry(), transfers);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fastcgi_pool() {
+        let pass = responder(FCGI_REQUEST_COMPLETE);
+        let mut poll = Poll::new().unwrap();
+        let mut pool = FastCgiPool::default();
+        let mut transfers = HashMap::new();
+
+        // Deux requêtes simultanées: une connexion chacune, l'application ne multiplexe pas
+        run(&mut pool, &mut poll, &mut transfers, &[1, 2], &pass);
+        assert_eq!(pool.connections.len(), 2);
+        assert_eq!(pool.multiplexed.get(&pass), Some(&false));
+        for client in [1, 2] {
+            let Some(Transfer::FastCgi(transfer)) = transfers.get_mut(&Token(client)) else {
+                unreachable!()
+            };
+            let mut output = vec![];
+            assert!(transfer.advance(&mut output).unwrap());
+            let output = String::from_utf8_lossy(&output);
+            assert!(output.starts_with("HTTP/1.1 200"), "{output}");
+            assert!(output.contains("hello"));
+        }
+
+        // Requête suivante: une connexion inactive est réutilisée
+        run(&mut pool, &mut poll, &mut transfers, &[3], &pass);
+        assert_eq!(pool.connections.len(), 2);
+
+        // FCGI_OVERLOADED -> 503
+        let pass = responder(FCGI_OVERLOADED);
+        run(&mut pool, &mut poll, &mut transfers, &[4], &pass);
+        let Some(Transfer::FastCgi(overloaded)) = transfers.get(&Token(4)) else {
+            unreachable!()
+        };
+        assert_eq!(overloaded.response.status, 503);
+
+        // Requête abandonnée: sa connexion reste occupée jusqu'à FCGI_END_REQUEST
+        let pass = responder(FCGI_REQUEST_COMPLETE);
+        let mut poll = Poll::new().unwrap();
+        let mut pool = FastCgiPool::default();
+        let Transfer::FastCgi(mut aborted) = transfer(&pass) else {
+            unreachable!()
+        };
+        pool.attach(poll.registry(), || Token(200), Token(5), &mut aborted)
+            .unwrap();
+        pool.abort(&aborted);
+        run(&mut pool, &mut poll, &mut transfers, &[6], &pass);
+        assert_eq!(pool.connections.len(), 2);
+        let busy = aborted.connection.unwrap();
+        let mut events = Events::with_capacity(16);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !pool.connections[&busy].is_idle() {
+            assert!(Instant::now() < deadline, "pas de FCGI_END_REQUEST");
+            poll.poll(&mut events, Some(Duration::from_millis(100)))
+                .unwrap();
+            pool.ready(busy, poll.registry(), &mut transfers);
+        }
+    }
+
+    #[test]
+    fn test_fastcgi_records() {
+        let bytes = FastCgiRecord::encode(FCGI_STDOUT, 3, b"hello");
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(&bytes[..8], &[1, FCGI_STDOUT, 0, 3, 0, 5, 3, 0]);
+        let (record, length) = FastCgiRecord::parse(&bytes).unwrap();
+        assert_eq!(length, 16);
+        assert_eq!(record.id, 3);
+        assert_eq!(record.content, b"hello");
+        assert!(FastCgiRecord::parse(&bytes[..10]).is_none());
+
+        // Flux long découpé, fin de flux vide
+        let long = vec![7; FCGI_MAX_CONTENT + 10];
+        let bytes = FastCgiRecord::encode(FCGI_STDIN, 1, &long);
+        let (first, length) = FastCgiRecord::parse(&bytes).unwrap();
+        let (second, _) = FastCgiRecord::parse(&bytes[length..]).unwrap();
+        assert_eq!(first.content.len() + second.content.len(), long.len());
+        assert_eq!(FastCgiRecord::encode(FCGI_STDIN, 1, &[]).len(), 8);
+    }
+
+    #[test]
+    fn test_encode_params() {
+        let long = "x".repeat(200);
+        let params = HashMap::from([("A".to_string(), long.clone())]);
+        let bytes = encode_params(&params);
+        assert_eq!(bytes[0], 1);
+        assert_eq!(&bytes[1..5], &(200u32 | 0x8000_0000).to_be_bytes());
+        assert_eq!(&bytes[5..6], b"A");
+        assert_eq!(&bytes[6..], long.as_bytes());
+        assert_eq!(decode_params(&bytes), params);
+    }
+}
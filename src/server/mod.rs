@@ -1,14 +1,14 @@
 pub mod request;
-use chrono::Utc;
+use chrono::{DateTime, Local, Utc};
 use mio::net::TcpStream;
 use regex::RegexSet;
 pub use request::*;
 use std::collections::HashMap;
-use std::fs::{OpenOptions, ReadDir};
+use std::fs::OpenOptions;
 // use std::io::{Error, Read};
 pub use std::string::String;
 // use std::time::{Duration, Instant};
-use std::{fs, io, io::Write, path::Path};
+use std::{fs, io, io::Write, path::Path, path::PathBuf};
 
 pub mod response;
 pub use response::*;
@@ -18,13 +18,36 @@ pub use router::*;
 pub mod session;
 pub use session::*;
 use tera::{Context, Tera};
+pub mod archive;
+pub mod cache;
 pub mod cgi;
+pub mod fastcgi;
+pub mod proxy;
 pub mod rendering_page;
+pub mod scgi;
+pub mod transfer;
+pub mod tus;
+pub mod upload;
+pub mod upstream;
+pub mod webdav;
 
+pub use archive::*;
+pub use cache::*;
 pub use cgi::*;
+pub use fastcgi::*;
+pub use proxy::*;
 pub use rendering_page::*;
+pub use scgi::*;
+pub use transfer::*;
+pub use tus::*;
+pub use upload::*;
+pub use upstream::*;
+pub use webdav::*;
 
-use crate::{remove_prefix, remove_suffix, Config, Redirection};
+use crate::{
+    copy_recursive, get_mime_type, remove_prefix, remove_suffix, url_encode, url_encode_path, Config,
+    Redirection, UploadPolicy,
+};
 
 #[derive(Debug)]
 pub enum ServerError<'a> {
@@ -32,6 +55,7 @@ pub enum ServerError<'a> {
     TeraError(&'a tera::Error),
     TomlError(&'a toml::de::Error),
     RegexError(&'a regex::Error),
+    JsonError(&'a serde_json::Error),
 }
 
 // -------------------------------------------------------------------------------------
@@ -50,6 +74,42 @@ pub struct Server {
     pub directory_listing: bool,
     pub redirections: Vec<Redirection>,
     pub exclusion: Vec<String>,
+    #[serde(default)]
+    pub archive_limit: usize, // kb, 0 = pas de limite
+    #[serde(default)]
+    pub upload_staging: String, // dossier des envois tus, vide = tus désactivé
+    #[serde(default = "default_upload_expiry")]
+    pub upload_expiry: u64, // secondes
+    #[serde(default)]
+    pub upload_policies: Vec<UploadPolicy>,
+    #[serde(default)]
+    pub upload_quota: u64, // kb sous root_directory, 0 = pas de limite
+    #[serde(default)]
+    pub session_upload_quota: u64, // kb par session et par fenêtre, 0 = pas de limite
+    #[serde(default = "default_quota_window")]
+    pub session_quota_window: u64, // secondes
+    #[serde(default = "default_cgi_handlers")]
+    pub cgi_handlers: HashMap<String, String>, // extension -> interpréteur
+    #[serde(default)]
+    pub cgi_locations: Vec<CgiLocation>,
+    #[serde(default)]
+    pub cgi_bin: String, // dossier (URL) des exécutables CGI, vide = désactivé
+    #[serde(default = "default_cgi_timeout")]
+    pub cgi_timeout: u64, // secondes, 0 = pas de limite
+    #[serde(default)]
+    pub cgi_limits: CgiLimits,
+    #[serde(default)]
+    pub fastcgi_locations: Vec<GatewayLocation>,
+    #[serde(default)]
+    pub scgi_locations: Vec<GatewayLocation>,
+    #[serde(default)]
+    pub uwsgi_locations: Vec<GatewayLocation>,
+    #[serde(default)]
+    pub proxy_locations: Vec<ProxyLocation>,
+    #[serde(default)]
+    pub cache_locations: Vec<ResponseCacheLocation>,
+    #[serde(default)]
+    pub session_cookie: SessionCookie,
 }
 
 impl Server {
@@ -78,6 +138,24 @@ impl Server {
             directory_listing,
             redirections,
             exclusion,
+            archive_limit: 0,
+            upload_staging: String::new(),
+            upload_expiry: default_upload_expiry(),
+            upload_policies: vec![],
+            upload_quota: 0,
+            session_upload_quota: 0,
+            session_quota_window: default_quota_window(),
+            cgi_handlers: default_cgi_handlers(),
+            cgi_locations: vec![],
+            cgi_bin: String::new(),
+            cgi_timeout: default_cgi_timeout(),
+            cgi_limits: CgiLimits::default(),
+            fastcgi_locations: vec![],
+            scgi_locations: vec![],
+            uwsgi_locations: vec![],
+            proxy_locations: vec![],
+            cache_locations: vec![],
+            session_cookie: SessionCookie::default(),
         }
     }
 
@@ -189,6 +267,28 @@ impl Server {
         }
     }
 
+    /// Journalise l'état du cache statique (HIT/MISS et compteurs) dans le fichier d'accès.
+    pub fn cache_log(config: &Config, path: &str, hit: bool, cache: &StaticCache) {
+        let str = format!(
+            "[{}] - CACHE {} {} - hits: {} misses: {} - {} kb used\n",
+            Utc::now().format("%d-%m-%Y %H:%M:%S"),
+            if hit { "HIT " } else { "MISS" },
+            path,
+            cache.hits,
+            cache.misses,
+            cache.used / 1024
+        );
+
+        if let Ok(mut log_file) = OpenOptions::new()
+            .append(true)
+            .open(&config.log_files.access_log)
+        {
+            if let Err(e) = log_file.write_all(str.as_bytes()) {
+                eprintln!("Writing error. Err: {}", e);
+            }
+        }
+    }
+
     pub fn handle_redirection(
         &self,
         request: &Request,
@@ -233,15 +333,47 @@ impl Server {
         }
     }
 
+    /// Sert la réponse depuis le cache de la location tant qu'elle y est valable; sinon
+    /// traite la requête et garde la réponse du script ou du serveur amont.
     pub fn handle_request(
+        &self,
+        stream: &mut TcpStream,
+        request: Request,
+        cookie: String,
+        config: &Config,
+        state: &mut ServerState,
+    ) -> Option<Transfer> {
+        let Some(cache) = self.cache_location(&request) else {
+            return self.dispatch_request(stream, request, cookie, config, state);
+        };
+        let key = cache.cache_key(&request);
+        match state.response_cache.lookup(cache, &key, &request) {
+            CacheLookup::Fresh(entry) => {
+                return self.send_cached(stream, &request, config, &cookie, entry, "HIT");
+            }
+            CacheLookup::Stale(entry) => {
+                let capture = CacheCapture::new(cache, &key);
+                self.revalidate(stream, &request, &cookie, config, state, capture);
+                return self.send_cached(stream, &request, config, &cookie, entry, "STALE");
+            }
+            CacheLookup::Miss => {}
+        }
+
+        let mut transfer = self.dispatch_request(stream, request, cookie, config, state);
+        if let Some(response) = transfer.as_mut().and_then(Transfer::cgi_response) {
+            response.capture = Some(CacheCapture::new(cache, &key));
+        }
+        transfer
+    }
+
+    fn dispatch_request(
         &self,
         mut stream: &mut TcpStream,
         mut request: Request,
         cookie: String,
         config: &Config,
-    ) {
- 
-
+        state: &mut ServerState,
+    ) -> Option<Transfer> {
         // Vérification de la méthode
         if !self
             .accepted_methods
@@ -257,7 +389,7 @@ impl Server {
                 "Method Not Allowed",
                 &cookie,
             );
-            return;
+            return None;
         }
 
         // Size limit
@@ -271,24 +403,56 @@ impl Server {
                 "Content Too Large",
                 &cookie,
             );
-            return;
+            return None;
+        }
+
+        // Reverse proxy: toutes les méthodes sont transmises au serveur amont
+        if let Some(location) = self.proxy_location(&request.path) {
+            return self.handle_proxy(stream, &request, location, &cookie, &mut state.upstreams);
+        }
+
+        // Envois resumables (tus)
+        if Self::is_tus_path(&request.path) {
+            return self.handle_tus(stream, &request, &cookie, config, state);
+        }
+
+        // Méthodes WebDAV (PROPFIND, MKCOL, PUT, LOCK...)
+        if Self::is_webdav_method(&request.method) {
+            return self.handle_webdav(stream, &request, &cookie, config, state);
         }
 
         self.handle_redirection(&request, stream, config, &cookie);
 
+        // Applications FastCGI (php-fpm, flup...), SCGI et uwsgi
+        if let Some((script, gateway)) = self.gateway_script(&self.fastcgi_locations, &request.path) {
+            return self.handle_fastcgi(stream, &request, &script, &gateway, &cookie);
+        }
+        if let Some((script, gateway)) = self.gateway_script(&self.scgi_locations, &request.path) {
+            return self.handle_scgi(stream, &request, &script, &gateway, ScgiProtocol::Scgi, &cookie);
+        }
+        if let Some((script, gateway)) = self.gateway_script(&self.uwsgi_locations, &request.path) {
+            return self.handle_scgi(stream, &request, &script, &gateway, ScgiProtocol::Uwsgi, &cookie);
+        }
+
+        // Scripts CGI: "/script.rb/path/info?query"
+        if request.method == "GET" || request.method == "POST" {
+            if let Some(script) = self.cgi_script(&request.path) {
+                return self.handle_cgi(stream, &request, &script, &cookie, config);
+            }
+        }
+
         let location_path;
         // Chemin réel du fichier
         let mut root = self.root_directory.clone();
         root = remove_suffix(root, "/");
 
-        let location = "./".to_string() + &root + &request.location;
+        let location = "./".to_string() + &root + &request.path;
 
         let discover = fs::read_dir(&location);
-        let entries: ReadDir;
-        let all;
+        let mut all;
         let mut dir_path;
-        if !request.location.contains(".")
-            && !request.location.contains("?")
+        if !request.path.contains(".")
+            && !request.query.contains_key("foldername")
             && request.method == "GET"
         {
             if !Path::new(&location.trim_end_matches("/")).exists() {
@@ -301,12 +465,12 @@ impl Server {
                     "Not Found x",
                     &cookie,
                 );
-                return;
+                return None;
             }
             location_path = "/index.html".to_string();
             dir_path = "src/static_files".to_string();
         } else {
-            location_path = Self::check_and_clean_path(&request.location);
+            location_path = Self::check_and_clean_path(&request.path);
             dir_path = self.root_directory.clone();
         }
 
@@ -320,90 +484,64 @@ impl Server {
             remove_prefix(location_path, "/")
         ); // Chemin relatif au dossier public
 
-        if !discover.is_err() && request.method == "GET" {
-            entries = discover.unwrap();
-            all = entries
-                .filter_map(|entry| {
-                    let el = entry.unwrap().path();
-                    let name = el
-                        .to_str()
-                        .unwrap()
-                        .strip_prefix(&location)
-                        .unwrap()
-                        .to_string();
-                    let re_init = RegexSet::new(&self.exclusion);
-                    if re_init.is_err() {
-                        Self::error_log(
-                            &request,
-                            config,
-                            "handle_request",
-                            file!(),
-                            line!(),
-                            ServerError::RegexError(&re_init.err().unwrap()),
-                        );
-                        return None;
-                    }
+        if discover.is_ok()
+            && request.method == "GET"
+            && !request.query.contains_key("foldername")
+        {
+            // "/dir" -> "/dir/" pour que les liens relatifs du listing soient corrects
+            if !request.path.ends_with('/') {
+                let mut target = url_encode_path(&request.path) + "/";
+                if let Some((_, query)) = request.location.split_once('?') {
+                    target = target + "?" + query;
+                }
+                if let Err(e) = self.send_redirect_response(stream, &target) {
+                    Self::error_log(
+                        &request,
+                        config,
+                        "handle_request",
+                        file!(),
+                        line!(),
+                        ServerError::IOError(&e),
+                    );
+                }
+                return None;
+            }
 
-                    let re = re_init.unwrap();
-
-                    match (el.is_file() && !re.is_match(&name))
-                        || (el.is_dir() && self.directory_listing && !re.is_match(&name))
-                    {
-                        true => {
-                            let entry_name = remove_prefix(name.clone(), "/");
-
-                            Some(DirectoryElement {
-                                entry: entry_name.clone(),
-                                entry_type: match el.is_dir() {
-                                    true => "folder".to_string(),
-                                    _ => {
-                                        let filename_parts =
-                                            entry_name.split(".").collect::<Vec<&str>>();
-                                        match filename_parts.len() {
-                                            2 => {
-                                                let ext = format!("{}{}", ".", filename_parts[1]);
-                                                let mut file_formats: HashMap<&str, &str> =
-                                                    HashMap::new();
-                                                file_formats.insert(".rb", "ruby");
-                                                file_formats.insert(".jpg", "image");
-                                                file_formats.insert(".jpeg", "image");
-                                                file_formats.insert(".png", "image");
-                                                file_formats.insert(".txt", "text");
-
-                                                match file_formats.get(ext.as_str()) {
-                                                    Some(filetype) => filetype.to_string(),
-                                                    None => "file".to_string(),
-                                                }
-                                            }
-                                            _ => "file".to_string(),
-                                        }
-                                    }
-                                },
-                                link: request.location.clone() + &name,
-                                is_directory: el.is_dir(),
-                            })
-                        }
-                        false => None,
-                    }
-                })
-                .collect::<Vec<DirectoryElement>>();
+            // Téléchargement du dossier complet: ?download=zip|tar.gz
+            if let Some(download) = request.query.get("download") {
+                return self.handle_archive_download(stream, &request, config, &location, download, &cookie);
+            }
 
-            self.handle_listing_directory(&mut stream, all, cookie, request.clone(), config);
-            return;
+            all = self.read_directory(&request, config, &location);
+            DirectoryElement::sort_and_filter(&mut all, &request.query);
+
+            let quota = self.quota_status(&request.id_session, &mut state.upload_usage);
+            self.handle_listing_directory(&mut stream, all, quota, cookie, request.clone(), config);
+            return None;
         }
 
         let fieldname = Request::extract_field(&request, "name");
+        let form = Request::form_fields(&request);
         println!("arret possible");
 
-        if request.clone().location.contains("?") {
+        if request.query.contains_key("foldername") {
             let _ = self.create_folder(stream, &request.clone(), &*cookie.clone(), config);
+        } else if request.method == "POST" && form.contains_key("file_to_move") {
+            self.move_elem(stream, &request, &form, &cookie, config, &mut state.upload_usage);
         } else if request.clone().method == "POST" && fieldname == String::from("file_to_delete") {
-            let _ = self.delete_elem(stream, &request.clone(), &*cookie.clone(), config);
+            let _ = self.delete_elem(stream, &request.clone(), &*cookie.clone(), config, &mut state.upload_usage);
         } else if request.clone().method == "POST" {
-            self.upload_file(stream, &mut request, config)
+            self.upload_file(stream, &mut request, config, &mut state.upload_usage)
         } else if Path::new(&path).exists() {
             // Servir un fichier statique
-            self.handle_static_file(request.clone(), config, &mut stream, &path, cookie);
+            return self.handle_static_file(
+                request.clone(),
+                config,
+                &mut stream,
+                &path,
+                cookie,
+                &mut state.static_cache,
+            );
         } else {
             // Ressource introuvable
             Self::send_error_response(
@@ -416,6 +554,245 @@ impl Server {
                 &cookie,
             );
         }
+        None
+    }
+
+    /// Construit les éléments du listing de `location` (chemin disque du dossier `request.path`),
+    /// en appliquant les regex d'exclusion du serveur.
+    fn read_directory(&self, request: &Request, config: &Config, location: &str) -> Vec<DirectoryElement> {
+        let entries = match fs::read_dir(location) {
+            Ok(entries) => entries,
+            Err(e) => {
+                Self::error_log(
+                    request,
+                    config,
+                    "read_directory",
+                    file!(),
+                    line!(),
+                    ServerError::IOError(&e),
+                );
+                return vec![];
+            }
+        };
+        let re = match RegexSet::new(&self.exclusion) {
+            Ok(re) => re,
+            Err(e) => {
+                Self::error_log(
+                    request,
+                    config,
+                    "read_directory",
+                    file!(),
+                    line!(),
+                    ServerError::RegexError(&e),
+                );
+                return vec![];
+            }
+        };
+
+        let base = remove_suffix(request.path.clone(), "/") + "/";
+        entries
+            .filter_map(|entry| {
+                let el = entry.ok()?.path();
+                let entry_name = el.file_name()?.to_string_lossy().to_string();
+
+                if re.is_match(&entry_name) || !(el.is_file() || (el.is_dir() && self.directory_listing)) {
+                    return None;
+                }
+
+                let metadata = el.metadata().ok();
+                let size = match el.is_dir() {
+                    true => 0,
+                    false => metadata.as_ref().map_or(0, |m| m.len()),
+                };
+                let modified: Option<DateTime<Local>> = metadata
+                    .and_then(|m| m.modified().ok())
+                    .map(DateTime::from);
+
+                Some(DirectoryElement {
+                    entry: entry_name.clone(),
+                    entry_type: match el.is_dir() {
+                        true => "folder".to_string(),
+                        _ => {
+                            let filename_parts = entry_name.split(".").collect::<Vec<&str>>();
+                            match filename_parts.len() {
+                                2 => {
+                                    let ext = format!("{}{}", ".", filename_parts[1]);
+                                    let mut file_formats: HashMap<&str, &str> = HashMap::new();
+                                    file_formats.insert(".rb", "ruby");
+                                    file_formats.insert(".jpg", "image");
+                                    file_formats.insert(".jpeg", "image");
+                                    file_formats.insert(".png", "image");
+                                    file_formats.insert(".txt", "text");
+
+                                    match file_formats.get(ext.as_str()) {
+                                        Some(filetype) => filetype.to_string(),
+                                        None => "file".to_string(),
+                                    }
+                                }
+                                _ => "file".to_string(),
+                            }
+                        }
+                    },
+                    link: match el.is_dir() {
+                        true => format!("{}{}/", base, url_encode(&entry_name)),
+                        false => format!("{}{}", base, url_encode(&entry_name)),
+                    },
+                    is_directory: el.is_dir(),
+                    size,
+                    human_size: match el.is_dir() {
+                        true => String::from("-"),
+                        false => DirectoryElement::human_size(size),
+                    },
+                    modified: modified
+                        .map(|d| d.format("%d-%m-%Y %H:%M:%S").to_string())
+                        .unwrap_or_default(),
+                    modified_timestamp: modified.map_or(0, |d| d.timestamp()),
+                    mime_type: match el.is_dir() {
+                        true => String::from("inode/directory"),
+                        false => get_mime_type(&entry_name).to_string(),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Envoie une archive du dossier `location`, construite à la volée pendant l'envoi.
+    fn handle_archive_download(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        config: &Config,
+        location: &str,
+        download: &str,
+        cookie: &String,
+    ) -> Option<Transfer> {
+        let Some(format) = ArchiveFormat::from_query(download) else {
+            self.send_error_response(stream, request, config, 400, "Bad Request: Unknown archive format", cookie);
+            return None;
+        };
+
+        let mut entries = vec![];
+        let collected = RegexSet::new(&self.exclusion)
+            .map_err(|e| {
+                Self::error_log(
+                    request,
+                    config,
+                    "handle_archive_download",
+                    file!(),
+                    line!(),
+                    ServerError::RegexError(&e),
+                )
+            })
+            .and_then(|re| {
+                self.collect_archive_entries(&re, Path::new(location), "", &mut entries)
+                    .map_err(|e| {
+                        Self::error_log(
+                            request,
+                            config,
+                            "handle_archive_download",
+                            file!(),
+                            line!(),
+                            ServerError::IOError(&e),
+                        )
+                    })
+            });
+        if collected.is_err() {
+            self.send_error_response(stream, request, config, 500, "Internal Server Error", cookie);
+            return None;
+        }
+
+        // Taille maximale de l'archive
+        let total: u64 = entries.iter().map(|e| e.size).sum();
+        let too_large = (self.archive_limit > 0 && total > self.archive_limit as u64 * 1024)
+            || (format == ArchiveFormat::Zip
+                && (total > ArchiveTransfer::zip_limit() || entries.len() >= u16::MAX as usize));
+        if too_large {
+            self.send_error_response(stream, request, config, 413, "Content Too Large", cookie);
+            return None;
+        }
+
+        let name = request
+            .path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .filter(|n| !n.is_empty())
+            .unwrap_or(&self.hostname)
+            .replace('"', "");
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Disposition: attachment; filename=\"{}.{}\"\r\nTransfer-Encoding: chunked\r\n{}\r\n",
+            format.content_type(),
+            name,
+            format.extension(),
+            cookie
+        );
+
+        self.access_log(request, config, 200, cookie);
+        let transfer = Transfer::Archive(Box::new(ArchiveTransfer::new(
+            format,
+            entries,
+            head.into_bytes(),
+        )));
+        match transfer.start(stream) {
+            Ok(transfer) => transfer,
+            Err(e) => {
+                Self::error_log(
+                    request,
+                    config,
+                    "handle_archive_download",
+                    file!(),
+                    line!(),
+                    ServerError::IOError(&e),
+                );
+                None
+            }
+        }
+    }
+
+    /// Parcourt récursivement `dir` en appliquant les mêmes exclusions que le listing.
+    /// Les liens symboliques sont ignorés pour ne pas sortir de root_directory.
+    fn collect_archive_entries(
+        &self,
+        re: &RegexSet,
+        dir: &Path,
+        prefix: &str,
+        entries: &mut Vec<ArchiveEntry>,
+    ) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let file_type = entry.file_type()?;
+            if re.is_match(&name) || file_type.is_symlink() {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            let archive_name = match prefix.is_empty() {
+                true => name,
+                false => format!("{}/{}", prefix, name),
+            };
+            let modified = metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+
+            if file_type.is_dir() && self.directory_listing {
+                entries.push(ArchiveEntry {
+                    path: entry.path(),
+                    name: archive_name.clone(),
+                    is_directory: true,
+                    size: 0,
+                    modified,
+                });
+                self.collect_archive_entries(re, &entry.path(), &archive_name, entries)?;
+            } else if file_type.is_file() {
+                entries.push(ArchiveEntry {
+                    path: entry.path(),
+                    name: archive_name,
+                    is_directory: false,
+                    size: metadata.len(),
+                    modified,
+                });
+            }
+        }
+        Ok(())
     }
 
     fn create_folder(
@@ -484,6 +861,7 @@ impl Server {
         request: &Request,
         cookie: &str,
         config: &Config,
+        usage: &mut UploadUsage,
     ) {
         // 1. Construire le chemin du dossier
         let folder_path = format!(
@@ -512,18 +890,145 @@ impl Server {
             return;
         }
 
-        if Path::new(&folder_path).is_dir() {
+        let size = path_size(Path::new(&folder_path));
+        let removed = if Path::new(&folder_path).is_dir() {
             // Supprimer le dossier
-            let _ = fs::remove_dir_all(&folder_path);
+            fs::remove_dir_all(&folder_path)
         } else {
             // Supprimer le fichier
-            let _ = fs::remove_file(&folder_path);
+            fs::remove_file(&folder_path)
+        };
+        if removed.is_ok() {
+            usage.update_disk(&self.root_directory, 0, size);
         }
 
         // Rediriger l'utilisateur vers l'URL d'origine (sans les paramètres de requête)
         let _ = self.send_redirect_response(stream, &request.location);
     }
 
+    /// Renomme ou déplace un élément du dossier courant (formulaire `file_to_move` / `destination`).
+    /// Une destination existante n'est remplacée que si le champ `overwrite` est coché.
+    fn move_elem(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        form: &HashMap<String, String>,
+        cookie: &String,
+        config: &Config,
+        usage: &mut UploadUsage,
+    ) {
+        let source_name = form.get("file_to_move").cloned().unwrap_or_default();
+        let destination = form.get("destination").cloned().unwrap_or_default();
+        let overwrite = form
+            .get("overwrite")
+            .is_some_and(|v| matches!(v.as_str(), "on" | "true" | "T" | "1"));
+
+        let (Some(source), Some(target)) = (
+            self.resolve_path(&request.path, &source_name),
+            self.resolve_path(&request.path, destination.trim()),
+        ) else {
+            self.send_error_response(stream, request, config, 403, "Forbidden", cookie);
+            return;
+        };
+
+        // Règles des envois appliquées à la destination: nom, extensions, scripts, collisions et quotas
+        let (target, overwrite) = match self
+            .move_target(&source, &target, overwrite)
+            .and_then(|moved| self.check_upload_quota(&request.id_session, 0, usage).map(|_| moved))
+        {
+            Ok(moved) => moved,
+            Err((status_code, status_message)) => {
+                self.send_error_response(stream, request, config, status_code, status_message, cookie);
+                return;
+            }
+        };
+
+        // Une destination écrasée libère sa place
+        let replaced = path_size(&target);
+        match self.move_path(&source, &target, overwrite) {
+            Ok(_) => {
+                usage.update_disk(&self.root_directory, 0, replaced);
+                // Rediriger l'utilisateur vers le listing
+                if let Err(e) = self.send_redirect_response(stream, &request.path) {
+                    Self::error_log(request, config, "move_elem", file!(), line!(), ServerError::IOError(&e));
+                } else {
+                    self.access_log(request, config, 302, cookie);
+                }
+            }
+            Err((status_code, status_message)) => {
+                self.send_error_response(stream, request, config, status_code, status_message, cookie);
+            }
+        }
+    }
+
+    /// Déplace `source` vers `target` (deux chemins déjà validés par `resolve_path`).
+    /// Renvoie le code HTTP et le message d'erreur à envoyer en cas d'échec.
+    fn move_path(&self, source: &Path, target: &Path, overwrite: bool) -> Result<(), (u16, &'static str)> {
+        if !source.exists() {
+            return Err((404, "Not Found"));
+        }
+        if source == target || source == Path::new(&self.root_directory) {
+            return Err((403, "Forbidden"));
+        }
+        if target.starts_with(source) {
+            return Err((409, "Conflict: cannot move a folder into itself"));
+        }
+        if !target.parent().is_some_and(|p| p.is_dir()) {
+            return Err((409, "Conflict: destination folder does not exist"));
+        }
+        if target.exists() {
+            if !overwrite {
+                return Err((409, "Conflict: destination already exists"));
+            }
+            let removed = match target.is_dir() {
+                true => fs::remove_dir_all(target),
+                false => fs::remove_file(target),
+            };
+            if removed.is_err() {
+                return Err((500, "Internal Server Error"));
+            }
+        }
+
+        // rename échoue entre deux systèmes de fichiers: copie puis suppression
+        fs::rename(source, target)
+            .or_else(|_| {
+                copy_recursive(source, target)?;
+                match source.is_dir() {
+                    true => fs::remove_dir_all(source),
+                    false => fs::remove_file(source),
+                }
+            })
+            .map_err(|_| (500, "Internal Server Error"))
+    }
+
+    /// Résout `target` (absolu depuis la racine du site, ou relatif au dossier `base`)
+    /// en chemin disque, sans jamais sortir de root_directory.
+    pub fn resolve_path(&self, base: &str, target: &str) -> Option<PathBuf> {
+        if target.is_empty() {
+            return None;
+        }
+        let url_path = match target.starts_with('/') {
+            true => target.to_string(),
+            false => format!("{}/{}", base.trim_end_matches('/'), target),
+        };
+
+        let mut segments: Vec<&str> = vec![];
+        for segment in url_path.split('/') {
+            match segment {
+                "" | "." => continue,
+                ".." => {
+                    segments.pop()?;
+                }
+                s if s.contains('\\') || s.contains('\0') => return None,
+                s => segments.push(s),
+            }
+        }
+
+        let mut path = PathBuf::from(remove_suffix(self.root_directory.clone(), "/"));
+        path.extend(segments);
+        Some(path)
+    }
+
     fn handle_static_file(
         &self,
         request: Request,
@@ -531,61 +1036,85 @@ impl Server {
         stream: &mut TcpStream,
         path: &str,
         cookie: String,
-    ) {
+        cache: &mut StaticCache,
+    ) -> Option<Transfer> {
         // Déterminer le type de contenu en fonction de l'extension du fichier
-        let mut to_cgi = false;
-        let content_type = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
-            Some("html") => "text/html",
-            Some("css") => "text/css",
-            Some("js") => "application/javascript",
-            Some("png") => "image/png",
-            Some("jpg") | Some("jpeg") => "image/jpeg",
-            Some("gif") => "image/gif",
-            Some("json") => "application/json",
-            Some("rb") => {
-                to_cgi = true;
-                "text/plain"
-            }
-            _ => "text/plain", // Type par défaut
-        };
+        let content_type = get_mime_type(path);
+
+        // Fichier chaud: servi depuis le cache mémoire
+        if cache.is_enabled() {
+            match cache.get(path) {
+                Ok(Some((entry, hit))) => {
+                    let not_modified = request
+                        .headers
+                        .get("If-None-Match")
+                        .is_some_and(|etag| etag.split(',').any(|e| e.trim() == entry.etag));
+                    let response = if not_modified {
+                        format!(
+                            "HTTP/1.1 304 Not Modified\r\nETag: {}\r\n{}\r\n",
+                            entry.etag, cookie
+                        )
+                        .into_bytes()
+                    } else {
+                        let mut response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nETag: {}\r\n{}\r\n",
+                            content_type, entry.length, entry.etag, cookie
+                        )
+                        .into_bytes();
+                        response.extend_from_slice(&entry.content);
+                        response
+                    };
+                    let status = if not_modified { 304 } else { 200 };
+
+                    Self::cache_log(config, path, hit, cache);
+                    self.access_log(&request, config, status, &cookie);
 
-        // Lire le fichier
-        match fs::read(path) {
-            Ok(mut content) => {
-                if to_cgi {
-                    content = CGI::execute_file(path.to_string()).into();
+                    let transfer = Transfer::File(FileTransfer::from_bytes(response));
+                    return transfer.start(stream).unwrap_or_default();
                 }
+                Ok(None) => Self::cache_log(config, path, false, cache),
+                Err(e) => Self::error_log(
+                    &request,
+                    config,
+                    "handle_static_file",
+                    file!(),
+                    line!(),
+                    ServerError::IOError(&e),
+                ),
+            }
+        }
 
-                let response = format!(
+        // Ouvrir le fichier: le contenu est envoyé par morceaux depuis le descripteur
+        let opened = fs::File::open(path).and_then(|file| {
+            let length = file.metadata()?.len();
+            Ok((file, length))
+        });
+
+        match opened {
+            Ok((file, length)) => {
+                let head = format!(
                     "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}\r\n",
-                    content_type,
-                    content.len(),
-                    cookie
+                    content_type, length, cookie
                 );
+                let transfer = Transfer::File(FileTransfer::new(file, length, head.into_bytes()));
 
-                if let Err(e) = stream.write_all(response.as_bytes()) {
-                    Self::error_log(
-                        &request,
-                        config,
-                        "handle_static_file",
-                        file!(),
-                        line!(),
-                        ServerError::IOError(&e),
-                    );
-                } else {
-                    // Log request
-                    self.access_log(&request, config, 200, &cookie);
-                    let _ = stream.flush();
-                }
-                if let Err(e) = stream.write_all(&content) {
-                    Self::error_log(
-                        &request,
-                        config,
-                        "handle_static_file",
-                        file!(),
-                        line!(),
-                        ServerError::IOError(&e),
-                    );
+                // Log request
+                self.access_log(&request, config, 200, &cookie);
+
+                // Envoyer tout ce qui peut l'être maintenant, le reste attendra un événement WRITABLE
+                match transfer.start(stream) {
+                    Ok(transfer) => transfer,
+                    Err(e) => {
+                        Self::error_log(
+                            &request,
+                            config,
+                            "handle_static_file",
+                            file!(),
+                            line!(),
+                            ServerError::IOError(&e),
+                        );
+                        None
+                    }
                 }
             }
             Err(e) => {
@@ -606,69 +1135,127 @@ impl Server {
                     "Internal Server Error",
                     &cookie,
                 );
+                None
             }
         }
     }
 
-    /// Gère une requête pour un fichier statique.
+    /// Gère une requête de listing de dossier.
+    /// Le format (HTML, JSON ou texte) est choisi par `?format=` ou par l'en-tête Accept.
     fn handle_listing_directory(
         &self,
         stream: &mut TcpStream,
         all: Vec<DirectoryElement>,
+        quota: QuotaStatus,
         cookie: String,
         request: Request,
         config: &Config,
     ) {
-        // Chargement du template
-        let tera = Tera::new("src/**/*.html").unwrap();
-        let mut context = Context::new();
-        context.insert("elements", &all);
-        context.insert("size", &all.len());
-        context.insert("hostname", &self.hostname);
-
-        match tera.render(&self.default_file.strip_prefix("src/").unwrap(), &context) {
-            Ok(content) => {
-                let response = format!(
-                    "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n{}\r\n{}",
-                    content.len(),
-                    cookie,
-                    content
-                );
+        let content_type = match request.query.get("format").map(String::as_str) {
+            Some("json") => "application/json",
+            Some("text") | Some("txt") => "text/plain",
+            Some("html") => "text/html",
+            _ => request
+                .preferred_type(&["text/html", "application/json", "text/plain"])
+                .unwrap_or("text/html"),
+        };
 
-                if let Err(e) = stream.write_all(response.as_bytes()) {
-                    Self::error_log(
-                        &request,
-                        config,
-                        "handle_listing_directory",
-                        file!(),
-                        line!(),
-                        ServerError::IOError(&e),
-                    );
-                } else {
-                    // Log request
-                    self.access_log(&request, config, 200, &cookie);
-                    let _ = stream.flush();
+        let content = match content_type {
+            "application/json" => {
+                let listing = serde_json::json!({
+                    "hostname": self.hostname,
+                    "path": request.path,
+                    "parent": Breadcrumb::parent_link(&request.path),
+                    "size": all.len(),
+                    "upload_quota": quota,
+                    "elements": all,
+                });
+                match serde_json::to_string_pretty(&listing) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        Self::error_log(
+                            &request,
+                            config,
+                            "handle_listing_directory",
+                            file!(),
+                            line!(),
+                            ServerError::JsonError(&e),
+                        );
+                        Self::send_error_response(
+                            self,
+                            stream,
+                            &request,
+                            config,
+                            500,
+                            "Internal Server Error",
+                            &cookie,
+                        );
+                        return;
+                    }
                 }
             }
-            Err(e) => {
-                Self::error_log(
-                    &request,
-                    config,
-                    "handle_listing_directory",
-                    file!(),
-                    line!(),
-                    ServerError::TeraError(&e),
-                );
-                Self::send_error_response(
-                    &self,
-                    stream,
-                    &request,
-                    config,
-                    500,
-                    "Internal Server Error",
-                    &cookie,
-                );
+            // Un chemin par ligne
+            "text/plain" => all.iter().map(|el| el.link.clone() + "\n").collect(),
+            _ => {
+                // Chargement du template
+                let tera = Tera::new("src/**/*.html").unwrap();
+                let mut context = Context::new();
+                context.insert("elements", &all);
+                context.insert("size", &all.len());
+                context.insert("hostname", &self.hostname);
+                context.insert("current_path", &request.path);
+                context.insert("breadcrumbs", &Breadcrumb::from_path(&request.path));
+                context.insert("parent_link", &Breadcrumb::parent_link(&request.path));
+                context.insert("upload_quota", &quota);
+                context.insert("session", &request.session);
+
+                match tera.render(&self.default_file.strip_prefix("src/").unwrap(), &context) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        Self::error_log(
+                            &request,
+                            config,
+                            "handle_listing_directory",
+                            file!(),
+                            line!(),
+                            ServerError::TeraError(&e),
+                        );
+                        Self::send_error_response(
+                            self,
+                            stream,
+                            &request,
+                            config,
+                            500,
+                            "Internal Server Error",
+                            &cookie,
+                        );
+                        return;
+                    }
+                }
             }
+        };
+
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nVary: Accept\r\n{}\r\n{}",
+            content_type,
+            content.len(),
+            cookie,
+            content
+        );
+
+        if let Err(e) = stream.write_all(response.as_bytes()) {
+            Self::error_log(
+                &request,
+                config,
+                "handle_listing_directory",
+                file!(),
+                line!(),
+                ServerError::IOError(&e),
+            );
+        } else {
+            // Log request
+            self.access_log(&request, config, 200, &cookie);
+            let _ = stream.flush();
         }
     }
 
@@ -692,6 +1279,7 @@ impl Server {
                 status: status_message.to_string(),
             }),
         );
+        context.insert("session", &request.session);
 
         match tera.render(&self.error_path.strip_prefix("src/").unwrap(), &context) {
             Ok(content) => {
@@ -729,7 +1317,13 @@ impl Server {
         }
     }
 
-    fn upload_file(&self, stream: &mut TcpStream, request: &mut Request, config: &Config) {
+    fn upload_file(
+        &self,
+        stream: &mut TcpStream,
+        request: &mut Request,
+        config: &Config,
+        usage: &mut UploadUsage,
+    ) {
         // Vérifier si le nom du fichier est vide
         if !request.complete {
             self.send_error_response(
@@ -759,11 +1353,27 @@ impl Server {
         // Obtention du nom du fichier
         let filename = Request::extract_field(request, "filename");
 
-        // Créer le chemin du fichier
-        let filepath = format!("./{}{}/{}", self.root_directory, request.location, filename);
+        // Nom nettoyé, extension et collision vérifiées selon la règle du dossier
+        let filepath = match self.upload_target(&request.path, &filename) {
+            Ok(filepath) => filepath,
+            Err((status, message)) => {
+                self.send_error_response(stream, request, config, status, message, &request.id_session);
+                return;
+            }
+        };
+
+        let file_content = Request::extract_values(&request.body_byte,request.boundary.clone().unwrap_or_default());
 
-        // Ouvrir ou créer le fichier
-        let mut file = match OpenOptions::new().create(true).write(true).open(&filepath) {
+        // Quotas du serveur et de la session
+        let size = file_content.len() as u64;
+        if let Err((status, message)) = self.check_upload_quota(&request.id_session, size, usage) {
+            self.send_error_response(stream, request, config, status, message, &request.id_session);
+            return;
+        }
+
+        // Ouvrir ou créer le fichier (tronqué s'il existait déjà)
+        let replaced = path_size(&filepath);
+        let mut file = match OpenOptions::new().create(true).write(true).truncate(true).open(&filepath) {
             Ok(file) => file, // Déballer le fichier
             Err(err) => {
                 Self::error_log(
@@ -786,9 +1396,7 @@ impl Server {
             }
         };
 
-      //  let file_content = Request::extract_field(request, "value");
         println!("content-type{}", request.content_type);
-        let file_content = Request::extract_values(&request.body_byte,request.boundary.clone().unwrap_or_default());
         // Écrire le contenu du fichier
         if let Err(err) = file.write_all(&file_content) {
             Self::error_log(
@@ -809,6 +1417,7 @@ impl Server {
             );
             return;
         }
+        self.record_upload(&request.id_session, size, replaced, usage);
 
         // Envoyer une réponse de redirection
         match self.send_redirect_response(stream, &*request.location) {
@@ -0,0 +1,738 @@
+use mio::net::TcpStream;
+use mio::{Registry, Token};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::{
+    is_under, upstream_address, upstream_host, CgiOutput, CgiResponse, PeerId, PeerReport, Request,
+    Server, Transfer, UpstreamPools, UpstreamSocket, UpstreamTarget,
+};
+
+// -------------------------------------------------------------------------------------
+// REVERSE PROXY
+// -------------------------------------------------------------------------------------
+// Taille maximale lue sur le socket à chaque tour
+const PROXY_READ_SIZE: usize = 64 * 1024;
+// Taille maximale d'une ligne de taille (ou de trailer) en chunked
+const CHUNK_LINE_MAX: usize = 4096;
+
+// En-têtes propres à une connexion, jamais retransmis (RFC 9110, section 7.6.1)
+const HOP_BY_HOP: [&str; 9] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+pub fn default_proxy_timeout() -> u64 {
+    60
+}
+
+/// Location transmise à un ou plusieurs serveurs HTTP/1.1, essayés dans l'ordre tant que
+/// la connexion échoue. Une adresse est "127.0.0.1:3000", "http://backend:8080" ou
+/// "unix:/chemin/vers/socket". `upstream` désigne plutôt un groupe de [http.upstreams],
+/// réparti selon sa stratégie.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProxyLocation {
+    pub location: String,
+    #[serde(default)]
+    pub upstreams: Vec<String>,
+    #[serde(default)]
+    pub upstream: String, // nom du groupe, prioritaire sur upstreams
+    #[serde(default = "default_proxy_timeout")]
+    pub timeout: u64, // secondes sans réponse du serveur avant 504, 0 = pas de limite
+    #[serde(default)]
+    pub preserve_host: bool, // transmet le Host du client au lieu de celui du serveur
+    // Adresses de `upstreams` résolues au chargement de la configuration
+    #[serde(skip)]
+    pub targets: Vec<UpstreamTarget>,
+}
+
+/// Requête réécrite pour le serveur amont; seul Host dépend du serveur choisi.
+#[derive(Debug)]
+pub struct ProxyRequest {
+    method: String,
+    uri: String,
+    host: Option<String>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    has_body: bool,
+}
+
+impl ProxyRequest {
+    /// Reprend les en-têtes du client (sauf ceux de la connexion) et ajoute
+    /// X-Forwarded-For/-Proto/-Host et Forwarded (RFC 7239).
+    pub fn new(request: &Request, client_ip: &str, preserve_host: bool) -> Self {
+        let head = request.head.split("\r\n\r\n").next().unwrap_or_default();
+        let lines: Vec<(&str, &str)> = head
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .collect();
+        let value = |wanted: &str| {
+            lines
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(wanted))
+                .map(|(_, value)| *value)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        // Les en-têtes cités dans Connection sont eux aussi propres à la connexion
+        let connection = value("Connection").to_ascii_lowercase();
+        let listed: Vec<&str> = connection.split(',').map(str::trim).collect();
+        let original_host = value("Host");
+        let mut forwarded_for = value("X-Forwarded-For");
+        let mut forwarded = value("Forwarded");
+
+        let mut headers: Vec<(String, String)> = lines
+            .iter()
+            .filter(|(name, _)| {
+                let name = name.to_ascii_lowercase();
+                !HOP_BY_HOP.contains(&name.as_str())
+                    && !listed.contains(&name.as_str())
+                    // Réécrits ou recalculés par le proxy; le corps est déjà reçu en entier
+                    && !matches!(
+                        name.as_str(),
+                        "host"
+                            | "content-length"
+                            | "expect"
+                            | "x-forwarded-for"
+                            | "x-forwarded-proto"
+                            | "x-forwarded-host"
+                            | "forwarded"
+                    )
+            })
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        if !forwarded_for.is_empty() {
+            forwarded_for += ", ";
+        }
+        forwarded_for += client_ip;
+        // Les adresses IPv6 sont entre crochets et guillemets dans Forwarded
+        let node = match client_ip.contains(':') {
+            true => format!("\"[{}]\"", client_ip),
+            false => client_ip.to_string(),
+        };
+        if !forwarded.is_empty() {
+            forwarded += ", ";
+        }
+        forwarded += &format!("for={}", node);
+        if !original_host.is_empty() {
+            forwarded += &format!(";host=\"{}\"", original_host);
+            headers.push(("X-Forwarded-Host".to_string(), original_host.clone()));
+        }
+        forwarded += ";proto=http";
+        headers.push(("X-Forwarded-For".to_string(), forwarded_for));
+        headers.push(("X-Forwarded-Proto".to_string(), "http".to_string()));
+        headers.push(("Forwarded".to_string(), forwarded));
+
+        let body = request.raw_body().to_vec();
+        Self {
+            method: request.method.clone(),
+            uri: request.location.clone(),
+            host: Some(original_host).filter(|host| preserve_host && !host.is_empty()),
+            has_body: !body.is_empty() || request.content_length.is_some(),
+            headers,
+            body,
+        }
+    }
+
+    /// Requête HTTP/1.1 complète pour `upstream`; la connexion est fermée après la réponse.
+    pub fn encode(&self, upstream: &str) -> Vec<u8> {
+        let host = self.host.clone().unwrap_or_else(|| upstream_host(upstream));
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\n",
+            self.method, self.uri, host
+        );
+        for (name, value) in &self.headers {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+        if self.has_body {
+            head += &format!("Content-Length: {}\r\n", self.body.len());
+        }
+        head += "Connection: close\r\n\r\n";
+
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+/// Délimitation du corps de la réponse amont.
+#[derive(Debug)]
+enum ProxyBody {
+    /// Ligne de statut et en-têtes en cours de réception
+    Head(Vec<u8>),
+    Length(u64),
+    Chunked(ChunkedDecoder),
+    /// Pas de longueur annoncée: le corps s'arrête à la fermeture de la connexion
+    UntilClose,
+    Done,
+}
+
+#[derive(Debug, Default, PartialEq)]
+enum ChunkState {
+    #[default]
+    Size,
+    Data(u64),
+    DataEnd,
+    Trailer,
+    Done,
+}
+
+/// Décodage incrémental de `Transfer-Encoding: chunked` (les trailers sont ignorés).
+#[derive(Debug, Default)]
+pub struct ChunkedDecoder {
+    state: ChunkState,
+    line: Vec<u8>,
+}
+
+impl ChunkedDecoder {
+    /// Ajoute à `output` les données contenues dans `input`.
+    /// Renvoie `Ok(true)` une fois le dernier morceau (et les trailers) reçu.
+    pub fn decode(&mut self, mut input: &[u8], output: &mut Vec<u8>) -> Result<bool, String> {
+        while !input.is_empty() && self.state != ChunkState::Done {
+            if let ChunkState::Data(remaining) = self.state {
+                let taken = input.len().min(remaining as usize);
+                output.extend_from_slice(&input[..taken]);
+                input = &input[taken..];
+                self.state = match remaining - taken as u64 {
+                    0 => ChunkState::DataEnd,
+                    remaining => ChunkState::Data(remaining),
+                };
+                continue;
+            }
+
+            // Taille, fin de morceau ou trailer: une ligne entière est nécessaire
+            let Some(end) = input.iter().position(|b| *b == b'\n') else {
+                self.line.extend_from_slice(input);
+                if self.line.len() > CHUNK_LINE_MAX {
+                    return Err("chunk line too long".to_string());
+                }
+                return Ok(false);
+            };
+            self.line.extend_from_slice(&input[..end]);
+            input = &input[end + 1..];
+            let line = std::mem::take(&mut self.line);
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches('\r');
+
+            self.state = match self.state {
+                ChunkState::Size => {
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    match u64::from_str_radix(size, 16) {
+                        Ok(0) => ChunkState::Trailer,
+                        Ok(size) => ChunkState::Data(size),
+                        Err(_) => return Err(format!("invalid chunk size: {:?}", line)),
+                    }
+                }
+                ChunkState::DataEnd if line.is_empty() => ChunkState::Size,
+                ChunkState::DataEnd => return Err("missing CRLF after chunk".to_string()),
+                ChunkState::Trailer if line.is_empty() => ChunkState::Done,
+                ChunkState::Trailer => ChunkState::Trailer,
+                _ => unreachable!(),
+            };
+        }
+        Ok(self.state == ChunkState::Done)
+    }
+}
+
+/// Requête en cours vers un serveur HTTP amont, sur sa propre connexion. La réponse est
+/// relayée au client au fur et à mesure; le serveur suivant est essayé si la connexion
+/// échoue avant tout échange.
+#[derive(Debug)]
+pub struct ProxyTransfer {
+    request: ProxyRequest,
+    // Serveurs restant à essayer, avec leur adresse résolue et leur place dans un groupe
+    upstreams: VecDeque<(String, UpstreamTarget, Option<PeerId>)>,
+    upstream: String,
+    peer: Option<PeerId>,
+    // Connexions et échecs à reporter sur les groupes par le Router
+    reports: Vec<PeerReport>,
+    socket: Option<UpstreamSocket>,
+    // Nécessaires pour enregistrer le socket d'un autre serveur après un échec
+    registry: Option<Registry>,
+    token: Option<Token>,
+    // Requête encodée pour le serveur courant et position déjà écrite
+    outgoing: Vec<u8>,
+    written: usize,
+    received: bool,
+    body: ProxyBody,
+    timeout: u64,
+    deadline: Option<Instant>,
+    pub response: CgiResponse,
+}
+
+impl ProxyTransfer {
+    pub fn new(
+        request: ProxyRequest,
+        upstreams: Vec<(String, UpstreamTarget, Option<PeerId>)>,
+        timeout: u64,
+        response: CgiResponse,
+    ) -> Self {
+        let mut transfer = Self {
+            request,
+            upstreams: upstreams.into(),
+            upstream: String::new(),
+            peer: None,
+            reports: vec![],
+            socket: None,
+            registry: None,
+            token: None,
+            outgoing: vec![],
+            written: 0,
+            received: false,
+            body: ProxyBody::Head(vec![]),
+            timeout,
+            deadline: None,
+            response,
+        };
+        transfer.connect_next();
+        transfer
+    }
+
+    /// Enregistre le socket dans le Poll du Router. Renvoie le token utilisé, conservé
+    /// pour les connexions suivantes.
+    pub fn register(
+        &mut self,
+        registry: &Registry,
+        next_token: impl FnOnce() -> Token,
+    ) -> io::Result<Vec<Token>> {
+        let Some(socket) = &mut self.socket else {
+            return Ok(vec![]);
+        };
+        let token = next_token();
+        socket.register(registry, token)?;
+        self.registry = Some(registry.try_clone()?);
+        self.token = Some(token);
+        Ok(vec![token])
+    }
+
+    /// Instant où le serveur sera considéré comme muet, `None` sans limite ou une fois fermé.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline.filter(|_| self.socket.is_some())
+    }
+
+    /// Délai dépassé: 504 Gateway Timeout, ou fermeture si la réponse avait déjà commencé.
+    pub fn expire(&mut self) {
+        self.release(true);
+        let error = format!(
+            "upstream {} timed out after {}s",
+            self.upstream, self.timeout
+        );
+        self.response.fail(504, &error);
+    }
+
+    /// Envoie la requête au serveur et relaie sa réponse au client, sans bloquer.
+    /// Renvoie `Ok(true)` lorsque la réponse est terminée.
+    pub fn advance<W: Write>(&mut self, stream: &mut W) -> io::Result<bool> {
+        self.write_request();
+        loop {
+            if !self.response.flush(stream)? {
+                return Ok(false);
+            }
+            if self.response.finished {
+                return Ok(true);
+            }
+            if !self.read_response() {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Comptes rendus accumulés depuis le dernier appel.
+    pub fn take_reports(&mut self) -> Vec<PeerReport> {
+        std::mem::take(&mut self.reports)
+    }
+
+    /// Fin de la requête (réponse envoyée ou client parti): libère le serveur en cours.
+    pub fn finish(&mut self) -> Vec<PeerReport> {
+        self.release(false);
+        self.take_reports()
+    }
+
+    /// Se connecte au prochain serveur de la liste; 502 Bad Gateway s'il n'en reste aucun.
+    fn connect_next(&mut self) {
+        while let Some((upstream, target, peer)) = self.upstreams.pop_front() {
+            if let Some(peer) = &peer {
+                self.reports.push(PeerReport::Connected(peer.clone()));
+            }
+            self.peer = peer;
+            let socket = UpstreamSocket::connect(&target).and_then(|mut socket| {
+                if let (Some(registry), Some(token)) = (&self.registry, self.token) {
+                    socket.register(registry, token)?;
+                }
+                Ok(socket)
+            });
+            match socket {
+                Ok(socket) => {
+                    self.outgoing = self.request.encode(&upstream);
+                    self.written = 0;
+                    self.upstream = upstream;
+                    self.socket = Some(socket);
+                    self.touch();
+                    return;
+                }
+                Err(e) => {
+                    self.release(true);
+                    self.response
+                        .push_error(&format!("upstream {}: {}", upstream, e));
+                }
+            }
+        }
+        self.response.fail(502, "no upstream available");
+    }
+
+    /// Échec sur le socket: le serveur suivant est essayé si rien n'a encore été échangé.
+    fn upstream_error(&mut self, e: io::Error) {
+        self.release(true);
+        if self.written == 0 && !self.received {
+            self.response
+                .push_error(&format!("upstream {}: {}", self.upstream, e));
+            self.connect_next();
+            return;
+        }
+        self.response
+            .fail(502, &format!("upstream {}: {}", self.upstream, e));
+    }
+
+    /// Ferme la connexion au serveur courant et rend compte de son issue.
+    fn release(&mut self, failed: bool) {
+        if let (Some(mut socket), Some(registry)) = (self.socket.take(), &self.registry) {
+            let _ = socket.deregister(registry);
+        }
+        if let Some(peer) = self.peer.take() {
+            self.reports.push(PeerReport::Released { peer, failed });
+        }
+    }
+
+    fn touch(&mut self) {
+        self.deadline = Some(self.timeout)
+            .filter(|timeout| *timeout > 0)
+            .map(|timeout| Instant::now() + Duration::from_secs(timeout));
+    }
+
+    fn write_request(&mut self) {
+        while self.written < self.outgoing.len() {
+            let Some(socket) = &mut self.socket else {
+                return;
+            };
+            match socket.write(&self.outgoing[self.written..]) {
+                Ok(n) => {
+                    self.written += n;
+                    self.touch();
+                }
+                // Connexion TCP encore en cours d'établissement
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::NotConnected =>
+                {
+                    return
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return self.upstream_error(e),
+            }
+        }
+    }
+
+    /// Lit un morceau de la réponse. Renvoie `false` s'il n'y a rien à lire pour l'instant.
+    fn read_response(&mut self) -> bool {
+        let Some(socket) = &mut self.socket else {
+            return false;
+        };
+        let mut buffer = vec![0; PROXY_READ_SIZE];
+        match socket.read(&mut buffer) {
+            Ok(0) => self.end_of_stream(),
+            Ok(read) => {
+                self.received = true;
+                self.touch();
+                self.receive(&buffer[..read]);
+            }
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock
+                    || e.kind() == io::ErrorKind::NotConnected =>
+            {
+                return false
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => self.upstream_error(e),
+        }
+        true
+    }
+
+    /// Fermeture de la connexion par le serveur.
+    fn end_of_stream(&mut self) {
+        match self.body {
+            ProxyBody::UntilClose | ProxyBody::Done => {
+                self.release(false);
+                self.response.end();
+            }
+            ProxyBody::Head(_) => self.bad_response("closed the connection before responding"),
+            _ => self.bad_response("closed the connection early"),
+        }
+    }
+
+    /// Réponse amont inutilisable: compte comme un échec du serveur, 502 Bad Gateway.
+    fn bad_response(&mut self, error: &str) {
+        self.release(true);
+        self.response
+            .fail(502, &format!("upstream {}: {}", self.upstream, error));
+    }
+
+    /// Interprète les octets reçus selon l'état de la réponse.
+    fn receive(&mut self, data: &[u8]) {
+        let data = match &mut self.body {
+            ProxyBody::Head(head) => {
+                head.extend_from_slice(data);
+                match self.parse_head() {
+                    Some(rest) => rest,
+                    None => return,
+                }
+            }
+            _ => data.to_vec(),
+        };
+
+        let mut output = vec![];
+        let done = match &mut self.body {
+            ProxyBody::Length(remaining) => {
+                let taken = data.len().min(*remaining as usize);
+                output.extend_from_slice(&data[..taken]);
+                *remaining -= taken as u64;
+                Ok(*remaining == 0)
+            }
+            ProxyBody::Chunked(decoder) => decoder.decode(&data, &mut output),
+            ProxyBody::UntilClose => {
+                output = data;
+                Ok(false)
+            }
+            _ => Ok(true),
+        };
+        self.response.feed(&output);
+        match done {
+            Ok(true) => {
+                self.body = ProxyBody::Done;
+                self.release(false);
+                self.response.end();
+            }
+            Ok(false) => {}
+            Err(e) => self.bad_response(&e),
+        }
+    }
+
+    /// Une fois la section d'en-têtes complète, la transmet et détermine la longueur du corps.
+    /// Renvoie le début du corps déjà reçu.
+    fn parse_head(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let ProxyBody::Head(head) = &mut self.body else {
+                return None;
+            };
+            let Some((header_end, body_start)) = CgiOutput::header_end(head) else {
+                if head.len() > PROXY_READ_SIZE {
+                    self.bad_response("header section too large");
+                }
+                return None;
+            };
+            let text = String::from_utf8_lossy(&head[..header_end]).to_string();
+            let status = text
+                .lines()
+                .next()
+                .filter(|line| line.starts_with("HTTP/"))
+                .and_then(|line| line.split_whitespace().nth(1))
+                .and_then(|code| code.parse::<u16>().ok());
+            let rest = head.split_off(body_start);
+            head.clear();
+
+            let Some(status) = status else {
+                self.bad_response("invalid status line");
+                return None;
+            };
+            if (100..200).contains(&status) {
+                // Réponse intermédiaire (100 Continue...): la réponse finale suit
+                self.body = ProxyBody::Head(rest);
+                continue;
+            }
+
+            let header = |wanted: &str| {
+                text.lines().skip(1).find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.trim()
+                        .eq_ignore_ascii_case(wanted)
+                        .then(|| value.trim().to_ascii_lowercase())
+                })
+            };
+            self.body = if self.request.method == "HEAD" || status == 204 || status == 304 {
+                ProxyBody::Done
+            } else if header("Transfer-Encoding").is_some_and(|te| te.contains("chunked")) {
+                ProxyBody::Chunked(ChunkedDecoder::default())
+            } else if let Some(length) = header("Content-Length") {
+                match length.parse::<u64>() {
+                    Ok(length) => ProxyBody::Length(length),
+                    Err(_) => {
+                        self.bad_response("invalid Content-Length");
+                        return None;
+                    }
+                }
+            } else {
+                ProxyBody::UntilClose
+            };
+            // En-têtes de la connexion amont retirés, le reste est interprété par CgiResponse
+            let mut head = String::new();
+            for line in text.lines() {
+                let name = line.split_once(':').map_or("", |(name, _)| name.trim());
+                if !HOP_BY_HOP.contains(&name.to_ascii_lowercase().as_str()) {
+                    head += line;
+                    head += "\r\n";
+                }
+            }
+            head += "\r\n";
+            self.response.feed(head.as_bytes());
+            return Some(rest);
+        }
+    }
+}
+
+impl Server {
+    /// Résout une fois pour toutes les adresses des locations proxy et des applications
+    /// FastCGI, SCGI et uwsgi, au chargement de la configuration.
+    pub fn resolve_upstreams(&mut self) {
+        for location in &mut self.proxy_locations {
+            location.targets = location
+                .upstreams
+                .iter()
+                .map(|upstream| UpstreamTarget::resolve(&upstream_address(upstream)))
+                .collect();
+        }
+        for gateway in self
+            .fastcgi_locations
+            .iter_mut()
+            .chain(self.scgi_locations.iter_mut())
+            .chain(self.uwsgi_locations.iter_mut())
+        {
+            gateway.target = UpstreamTarget::resolve(&gateway.pass);
+        }
+    }
+
+    /// Location relayée à des serveurs HTTP amont (la plus longue correspondant au chemin).
+    pub fn proxy_location(&self, url_path: &str) -> Option<&ProxyLocation> {
+        self.proxy_locations
+            .iter()
+            .filter(|proxy| is_under(url_path, &proxy.location))
+            .max_by_key(|proxy| proxy.location.trim_end_matches('/').len())
+    }
+
+    /// Transmet la requête au premier serveur amont joignable (choisi dans le groupe de la
+    /// location s'il y en a un); la réponse est relayée par le Router au fil des événements
+    /// sur le socket.
+    pub fn handle_proxy(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        location: &ProxyLocation,
+        cookie: &str,
+        pools: &mut UpstreamPools,
+    ) -> Option<Transfer> {
+        let client_ip = stream
+            .peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_default();
+        let proxied = ProxyRequest::new(request, &client_ip, location.preserve_host);
+        let mut response = CgiResponse::new(
+            self.clone(),
+            request.clone(),
+            Path::new(&location.location),
+            cookie,
+        );
+        response.proxied = true;
+
+        let upstreams = match location.upstream.is_empty() {
+            true => location
+                .upstreams
+                .iter()
+                .enumerate()
+                .map(|(i, upstream)| {
+                    let target = location.targets.get(i).cloned().unwrap_or_default();
+                    (upstream.clone(), target, None)
+                })
+                .collect(),
+            false => match pools.select(&location.upstream, &client_ip) {
+                Some(peers) => peers
+                    .into_iter()
+                    .map(|(address, peer)| (address, pools.target(&peer), Some(peer)))
+                    .collect(),
+                None => {
+                    response.push_error(&format!("unknown upstream group {:?}", location.upstream));
+                    vec![]
+                }
+            },
+        };
+        let mut transfer = ProxyTransfer::new(proxied, upstreams, location.timeout, response);
+        // Compté tout de suite: les requêtes suivantes voient cette connexion (least_conn)
+        pools.report(transfer.take_reports());
+        Some(Transfer::Proxy(Box::new(transfer)))
+    }
+}
+// -------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunked_decoder() {
+        let mut decoder = ChunkedDecoder::default();
+        let mut output = vec![];
+        let input = b"5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nTrailer: x\r\n\r\n";
+        // Reçu en morceaux de 3 octets
+        let mut done = false;
+        for part in input.chunks(3) {
+            done = decoder.decode(part, &mut output).unwrap();
+        }
+        assert!(done);
+        assert_eq!(output, b"hello world");
+
+        let mut decoder = ChunkedDecoder::default();
+        assert!(decoder.decode(b"zz\r\n", &mut output).is_err());
+        let mut decoder = ChunkedDecoder::default();
+        assert!(decoder.decode(b"1\r\nab\r\n", &mut output).is_err());
+    }
+
+    #[test]
+    fn test_proxy_request() {
+        let mut request = Request::default();
+        request.method = "POST".to_string();
+        request.location = "/api/items?x=1".to_string();
+        request.head = "POST /api/items?x=1 HTTP/1.1\r\nHost: example.com:8080\r\n\
+            Connection: keep-alive, X-Secret\r\nX-Secret: 1\r\nAccept: */*\r\n\
+            X-Forwarded-For: 10.0.0.1\r\nContent-Length: 2"
+            .to_string();
+        request.body_byte = b"POST /api/items?x=1 HTTP/1.1\r\n\r\nok".to_vec();
+        request.content_length = Some(2);
+
+        let proxied = ProxyRequest::new(&request, "192.168.1.5", false);
+        let encoded = String::from_utf8(proxied.encode("http://127.0.0.1:3000")).unwrap();
+        assert_eq!(
+            encoded,
+            "POST /api/items?x=1 HTTP/1.1\r\nHost: 127.0.0.1:3000\r\nAccept: */*\r\n\
+            X-Forwarded-Host: example.com:8080\r\n\
+            X-Forwarded-For: 10.0.0.1, 192.168.1.5\r\nX-Forwarded-Proto: http\r\n\
+            Forwarded: for=192.168.1.5;host=\"example.com:8080\";proto=http\r\n\
+            Content-Length: 2\r\nConnection: close\r\n\r\nok"
+        );
+
+        let proxied = ProxyRequest::new(&request, "::1", true);
+        let encoded = String::from_utf8(proxied.encode("unix:/tmp/app.sock")).unwrap();
+        assert!(encoded.contains("Host: example.com:8080\r\n"));
+        assert!(encoded.contains("Forwarded: for=\"[::1]\";"));
+    }
+}
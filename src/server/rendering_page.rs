@@ -1,7 +1,11 @@
 pub use serde::{Deserialize, Serialize};
 
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::string::String;
 
+use crate::url_encode;
+
 // -------------------------------------------------------------------------------------
 // DIRECTORY ELEMENT
 // -------------------------------------------------------------------------------------
@@ -11,10 +15,93 @@ pub struct DirectoryElement {
     pub link: String,
     pub entry_type: String,
     pub is_directory: bool,
+    pub size: u64,
+    pub human_size: String,
+    pub modified: String,
+    pub modified_timestamp: i64,
+    pub mime_type: String,
 }
+
+impl DirectoryElement {
+    /// Trie et filtre le listing selon la query string:
+    /// `?sort=name|size|mtime&order=asc|desc&filter=texte`.
+    /// Les dossiers restent toujours en tête.
+    pub fn sort_and_filter(elements: &mut Vec<DirectoryElement>, query: &HashMap<String, String>) {
+        if let Some(filter) = query.get("filter").filter(|f| !f.is_empty()) {
+            let filter = filter.to_lowercase();
+            elements.retain(|el| el.entry.to_lowercase().contains(&filter));
+        }
+
+        let sort = query.get("sort").map(String::as_str).unwrap_or("name");
+        let descending = query.get("order").is_some_and(|o| o == "desc");
+
+        elements.sort_by(|a, b| {
+            let order = match sort {
+                "size" => a.size.cmp(&b.size),
+                "mtime" => a.modified_timestamp.cmp(&b.modified_timestamp),
+                _ => Ordering::Equal,
+            }
+            .then_with(|| a.entry.to_lowercase().cmp(&b.entry.to_lowercase()));
+
+            let order = if descending { order.reverse() } else { order };
+            b.is_directory.cmp(&a.is_directory).then(order)
+        });
+    }
+
+    /// Taille lisible par un humain ("1.4 MB").
+    pub fn human_size(size: u64) -> String {
+        let units = ["B", "KB", "MB", "GB", "TB"];
+        let mut value = size as f64;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < units.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        match unit {
+            0 => format!("{} {}", size, units[0]),
+            _ => format!("{:.1} {}", value, units[unit]),
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
+
 // -------------------------------------------------------------------------------------
+// BREADCRUMB
+// -------------------------------------------------------------------------------------
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Breadcrumb {
+    pub name: String,
+    pub link: String,
+}
 
+impl Breadcrumb {
+    /// Découpe "/d/g/" en [Home -> "/", d -> "/d/", g -> "/d/g/"].
+    pub fn from_path(path: &str) -> Vec<Breadcrumb> {
+        let mut link = String::from("/");
+        let mut crumbs = vec![Breadcrumb {
+            name: String::from("Home"),
+            link: link.clone(),
+        }];
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            link = link + &url_encode(segment) + "/";
+            crumbs.push(Breadcrumb {
+                name: segment.to_string(),
+                link: link.clone(),
+            });
+        }
+        crumbs
+    }
 
+    /// Lien vers le dossier parent, `None` à la racine.
+    pub fn parent_link(path: &str) -> Option<String> {
+        let crumbs = Self::from_path(path);
+        match crumbs.len() {
+            0 | 1 => None,
+            n => Some(crumbs[n - 2].link.clone()),
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
 
 // -------------------------------------------------------------------------------------
 // HTMLError
@@ -25,3 +112,70 @@ pub struct HTMLError {
     pub status: String,
 }
 // -------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(entry: &str, size: u64, modified_timestamp: i64, is_directory: bool) -> DirectoryElement {
+        DirectoryElement {
+            entry: entry.to_string(),
+            link: format!("/{}", entry),
+            entry_type: String::from("file"),
+            is_directory,
+            size,
+            human_size: DirectoryElement::human_size(size),
+            modified: String::new(),
+            modified_timestamp,
+            mime_type: String::from("text/plain"),
+        }
+    }
+
+    fn names(elements: &[DirectoryElement]) -> Vec<&str> {
+        elements.iter().map(|el| el.entry.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_directories_first() {
+        let mut elements = vec![
+            element("b.txt", 10, 1, false),
+            element("z", 0, 1, true),
+            element("a.txt", 30, 2, false),
+        ];
+        DirectoryElement::sort_and_filter(&mut elements, &HashMap::new());
+        assert_eq!(names(&elements), vec!["z", "a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn test_sort_by_size_desc_and_filter() {
+        let mut elements = vec![
+            element("small.png", 10, 1, false),
+            element("big.png", 300, 2, false),
+            element("notes.txt", 1000, 3, false),
+        ];
+        let query = HashMap::from([
+            ("sort".to_string(), "size".to_string()),
+            ("order".to_string(), "desc".to_string()),
+            ("filter".to_string(), "PNG".to_string()),
+        ]);
+        DirectoryElement::sort_and_filter(&mut elements, &query);
+        assert_eq!(names(&elements), vec!["big.png", "small.png"]);
+    }
+
+    #[test]
+    fn test_breadcrumbs_and_parent() {
+        let crumbs = Breadcrumb::from_path("/d/mon dossier/");
+        let links: Vec<&str> = crumbs.iter().map(|c| c.link.as_str()).collect();
+        assert_eq!(links, vec!["/", "/d/", "/d/mon%20dossier/"]);
+        assert_eq!(crumbs[2].name, "mon dossier");
+        assert_eq!(Breadcrumb::parent_link("/d/g/"), Some("/d/".to_string()));
+        assert_eq!(Breadcrumb::parent_link("/"), None);
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(DirectoryElement::human_size(512), "512 B");
+        assert_eq!(DirectoryElement::human_size(1536), "1.5 KB");
+        assert_eq!(DirectoryElement::human_size(5 * 1024 * 1024), "5.0 MB");
+    }
+}
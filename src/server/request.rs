@@ -3,7 +3,10 @@ use mio::net::TcpStream;
 use regex::Regex;
 use std::{collections::HashMap, io::Read};
 
-use crate::{get_boundary, get_content_length, remove_prefix, remove_suffix};
+use crate::{
+    get_boundary, get_content_length, normalize_path, percent_decode, remove_prefix, remove_suffix,
+    url_decode,
+};
 
 // -------------------------------------------------------------------------------------
 // REQUEST
@@ -14,6 +17,8 @@ pub struct Request {
     pub content_type: String,
     pub content_length: Option<usize>,
     pub location: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
     pub host: String,
     pub port: u16,
     pub method: String,
@@ -27,6 +32,9 @@ pub struct Request {
     pub complete: bool,
     pub headers: HashMap<String, String>,
     pub timestamp: i64,
+    pub redirects: u8, // redirections internes déjà suivies (CGI)
+    pub session: HashMap<String, String>, // données de la session (SessionStore)
+    pub cookies: HashMap<String, String>, // en-tête Cookie, par nom
 }
 
 impl Request {
@@ -49,6 +57,8 @@ impl Request {
             content_type,
             content_length: None,
             location,
+            path: String::new(),
+            query: HashMap::new(),
             host,
             port,
             method,
@@ -62,6 +72,9 @@ impl Request {
             complete: false,
             headers: HashMap::new(),
             timestamp: Utc::now().timestamp_millis(),
+            redirects: 0,
+            session: HashMap::new(),
+            cookies: HashMap::new(),
         }
     }
 
@@ -120,6 +133,12 @@ impl Request {
         let mut request = Request::default();
         let (request_str, body_byte) = Self::stream_to_str(stream);
         let mut is_post = false;
+        let mut has_raw_body = false;
+        let method = request_str
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
 
         if request_str.starts_with("GET") {
             request.complete = true;
@@ -128,6 +147,10 @@ impl Request {
         } else if request_str.starts_with("POST") {
             is_post = true;
             request.method = String::from("POST");
+        } else if Self::is_method(&method) {
+            // PUT, PATCH, DELETE, méthodes WebDAV...: corps brut, sans formulaire multipart
+            has_raw_body = true;
+            request.method = method;
         } else {
             request.body = request_str.clone();
             request.body_byte = body_byte.clone();
@@ -146,6 +169,20 @@ impl Request {
 
                 let mut form_data: Vec<HashMap<&str, Option<String>>> = vec![]; // Chaque HashMap représente un champ du formulaire.
 
+                if has_raw_body {
+                    let body_start = body_byte
+                        .windows(4)
+                        .position(|w| w == new_line_pattern.as_bytes())
+                        .map_or(body_byte.len(), |pos| pos + 4);
+                    request.head = headers.to_string();
+                    request.body_byte = body_byte[body_start..].to_vec();
+                    request.body = String::from_utf8_lossy(&request.body_byte).to_string();
+                    request.length = request.body_byte.len();
+                    request.content_length = get_content_length(&request.head)
+                        .and_then(|length| length.parse::<usize>().ok());
+                    request.complete = request.body_byte.len() >= request.content_length.unwrap_or(0);
+                }
+
                 if is_post {
                     let mut head = request_str.clone();
                     let mut body = head.split_off(header_limit);
@@ -310,18 +347,25 @@ impl Request {
 
         // Parser les en-têtes
         for line in lines.iter().skip(1) {
-            if line.starts_with("Host:") {
+            if let Some(value) = line.strip_prefix("Host:") {
                 let host_parts: Vec<&str> = line.split(":").collect();
                 host = host_parts[1].trim().to_string();
                 if host_parts.len() > 2 {
                     port = host_parts[2].parse::<u16>().unwrap_or(80);
                 }
+                headers.insert("Host".to_string(), value.trim().to_string());
             } else if line.contains(":") {
                 let mut parts = line.splitn(2, ":");
                 if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
                     let key = key.trim().trim_matches('"').to_string(); // Supprimer les espaces et les guillemets
-                    if key == "Cookie" {
-                        cookie = value.to_owned();
+                    if key.eq_ignore_ascii_case("Cookie") {
+                        // Plusieurs en-têtes Cookie sont réunis en un seul
+                        if !cookie.is_empty() {
+                            cookie.push(';');
+                        }
+                        cookie.push_str(value);
+                        headers.insert(key, cookie.trim().to_string());
+                        continue;
                     }
                     let value = value.trim().to_string(); // Supprimer les espaces
                     if !key.is_empty() && !value.is_empty() {
@@ -334,18 +378,138 @@ impl Request {
         let binding = Self::extract_header_value(&lines, "Referer:");
         let referer = binding.split(":").nth(1).unwrap_or_default();
 
+        let (path, query) = Self::split_location(&location);
         request.location = location;
-        request.id_session = cookie
-            .trim()
-            .strip_prefix("cookie_01=")
-            .unwrap_or_default()
-            .to_owned();
+        request.path = path;
+        request.query = query;
+        request.cookies = Self::parse_cookies(&cookie);
         request.host = host;
         request.port = port;
+        request.headers = headers;
         request.length = request.body.len();
         request.reference = referer.to_string();
     }
 
+    /// Méthodes qui ouvrent une nouvelle requête (les autres lectures sont la suite d'un corps).
+    pub fn is_method(method: &str) -> bool {
+        matches!(
+            method,
+            "GET"
+                | "HEAD"
+                | "POST"
+                | "PUT"
+                | "DELETE"
+                | "OPTIONS"
+                | "PROPFIND"
+                | "PROPPATCH"
+                | "MKCOL"
+                | "COPY"
+                | "MOVE"
+                | "LOCK"
+                | "UNLOCK"
+                | "PATCH"
+        )
+    }
+
+    /// Valeur d'un en-tête, sans tenir compte de la casse de son nom.
+    pub fn header(&self, name: &str) -> Option<&String> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Corps brut de la requête, sans les en-têtes (le corps d'un POST est stocké avec eux).
+    pub fn raw_body(&self) -> &[u8] {
+        let body = match self.method.as_str() {
+            "POST" => self
+                .body_byte
+                .windows(4)
+                .position(|w| w == b"\r\n\r\n")
+                .map_or(&self.body_byte[..], |pos| &self.body_byte[pos + 4..]),
+            _ => &self.body_byte[..],
+        };
+        &body[..self.content_length.unwrap_or(body.len()).min(body.len())]
+    }
+
+    /// Cookies d'un en-tête Cookie ("a=1; b=\"2\"" -> {a: 1, b: 2}, RFC 6265 section 5.4).
+    /// Un nom répété garde sa première valeur, la plus spécifique pour le navigateur.
+    pub fn parse_cookies(header: &str) -> HashMap<String, String> {
+        let mut cookies = HashMap::new();
+        for pair in header.split(';') {
+            let Some((name, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let name = name.trim();
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            if !name.is_empty() {
+                cookies
+                    .entry(name.to_string())
+                    .or_insert_with(|| value.to_string());
+            }
+        }
+        cookies
+    }
+
+    /// Sépare le chemin de la query string ("/dir?sort=size" -> "/dir", {sort: size}).
+    pub fn split_location(location: &str) -> (String, HashMap<String, String>) {
+        let mut parts = location.splitn(2, '?');
+        // Décodé puis normalisé: "%2e%2e" ne doit pas sortir de root_directory
+        let path = normalize_path(&percent_decode(parts.next().unwrap_or_default()));
+        let query = parts
+            .next()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let mut kv = pair.splitn(2, '=');
+                (
+                    url_decode(kv.next().unwrap_or_default()),
+                    url_decode(kv.next().unwrap_or_default()),
+                )
+            })
+            .collect();
+        (path, query)
+    }
+
+    /// Choisit parmi `offers` le type préféré du client d'après l'en-tête Accept (q-values).
+    /// Sans en-tête Accept, le premier type proposé est retenu.
+    pub fn preferred_type<'a>(&self, offers: &[&'a str]) -> Option<&'a str> {
+        let accept = match self.headers.get("Accept") {
+            Some(accept) if !accept.trim().is_empty() => accept,
+            _ => return offers.first().copied(),
+        };
+
+        let mut best: Option<(&str, f32)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';');
+            let media = params.next().unwrap_or_default().trim().to_lowercase();
+            let quality = params
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality <= 0.0 {
+                continue;
+            }
+
+            let matched = offers.iter().find(|offer| {
+                media == **offer
+                    || media == "*/*"
+                    || (media.ends_with("/*") && offer.starts_with(media.trim_end_matches('*')))
+            });
+            if let Some(offer) = matched {
+                if best.is_none_or(|(_, q)| quality > q) {
+                    best = Some((offer, quality));
+                }
+            }
+        }
+        best.map(|(offer, _)| offer)
+    }
+
     pub fn extract_header_value(headers: &[&str], pattern: &str) -> String {
         let mut header_value = String::new();
 
@@ -375,6 +539,22 @@ impl Request {
         }
         filename
     }
+
+    /// Renvoie les champs texte du formulaire multipart sous la forme nom -> valeur.
+    pub fn form_fields(request: &Request) -> HashMap<String, String> {
+        let mut form_data = vec![];
+        if let Some(boundary) = &request.boundary {
+            Request::extract_form_data(&request.body, boundary.to_string(), &mut form_data);
+        }
+        form_data
+            .into_iter()
+            .filter_map(|field| match (field.get("name"), field.get("value")) {
+                (Some(Some(name)), Some(Some(value))) => Some((name.to_owned(), value.to_owned())),
+                _ => None,
+            })
+            .collect()
+    }
+
     /*
        La fonction cherche sucessivement le paterne \r\n\r\n puis le bopundary
        et encore \r\n\r\n et separe a chaque fois !
@@ -402,3 +582,63 @@ impl Request {
         tmp[fist + 4..].to_vec()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_location() {
+        let (path, query) = Request::split_location("/mon%20dossier?sort=size&filter=a+b");
+        assert_eq!(path, "/mon dossier");
+        assert_eq!(query.get("sort").map(String::as_str), Some("size"));
+        assert_eq!(query.get("filter").map(String::as_str), Some("a b"));
+
+        // "+" n'est un espace que dans la query
+        let (path, query) = Request::split_location("/a+b%2B.txt?q=a%2Bb+c");
+        assert_eq!(path, "/a+b+.txt");
+        assert_eq!(query.get("q").map(String::as_str), Some("a+b c"));
+
+        // Remontée encodée bornée à la racine, barre finale conservée
+        let (path, _) = Request::split_location("/%2e%2e/%2E%2E/etc/passwd");
+        assert_eq!(path, "/etc/passwd");
+        let (path, _) = Request::split_location("/docs/./a/..%2f..%2f../");
+        assert_eq!(path, "/");
+        let (path, _) = Request::split_location("/docs//img/");
+        assert_eq!(path, "/docs/img/");
+    }
+
+    #[test]
+    fn test_parse_cookies() {
+        let cookies = Request::parse_cookies(" theme=dark;sid=\"abc=1\"; flag; =x; theme=light ");
+        assert_eq!(cookies.len(), 2);
+        assert_eq!(cookies["theme"], "dark");
+        assert_eq!(cookies["sid"], "abc=1");
+
+        let mut request = Request::default();
+        Request::parse_http_request(
+            "GET / HTTP/1.1\r\nHost: 127.0.0.1:8080\r\nCookie: lang=fr\r\ncookie: sid=42\r\n\r\n",
+            &mut request,
+        );
+        assert_eq!(request.cookies["lang"], "fr");
+        assert_eq!(request.cookies["sid"], "42");
+    }
+
+    #[test]
+    fn test_preferred_type() {
+        let offers = ["text/html", "application/json", "text/plain"];
+        let mut request = Request::default();
+        assert_eq!(request.preferred_type(&offers), Some("text/html"));
+
+        request.headers.insert(
+            "Accept".to_string(),
+            "text/html;q=0.5, application/json".to_string(),
+        );
+        assert_eq!(request.preferred_type(&offers), Some("application/json"));
+
+        request
+            .headers
+            .insert("Accept".to_string(), "text/*".to_string());
+        assert_eq!(request.preferred_type(&offers), Some("text/html"));
+    }
+}
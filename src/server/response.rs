@@ -1,3 +1,8 @@
+use mio::net::TcpStream;
+
+use super::{FileTransfer, Request, Server, ServerError, Transfer};
+use crate::Config;
+
 // -------------------------------------------------------------------------------------
 // RESPONSE
 // -------------------------------------------------------------------------------------
@@ -29,6 +34,45 @@ impl Response {
         format!("HTTP/1.1 {}\r\n{}\r\n{}", self.status, headers, self.body)
     }
 
+    /// Texte associé à un code de statut HTTP ("404" -> "Not Found").
+    pub fn status_text(status_code: u16) -> &'static str {
+        match status_code {
+            100 => "Continue",
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            204 => "No Content",
+            206 => "Partial Content",
+            207 => "Multi-Status",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            410 => "Gone",
+            412 => "Precondition Failed",
+            413 => "Content Too Large",
+            415 => "Unsupported Media Type",
+            423 => "Locked",
+            424 => "Failed Dependency",
+            429 => "Too Many Requests",
+            500 => "Internal Server Error",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            507 => "Insufficient Storage",
+            508 => "Loop Detected",
+            _ => "Unknown",
+        }
+    }
+
     // -------------------------------------------------------------------------------------
     // MÉTHODES D'ERREUR
     // -------------------------------------------------------------------------------------
@@ -92,4 +136,94 @@ impl Response {
             body: "403 Forbidden: You do not have permission to access this resource.".to_string(),
         }
     }
-}
\ No newline at end of file
+}
+// -------------------------------------------------------------------------------------
+
+// -------------------------------------------------------------------------------------
+// RAW RESPONSE
+// -------------------------------------------------------------------------------------
+/// Réponse construite en mémoire (WebDAV, tus...) avant sérialisation.
+#[derive(Debug)]
+pub struct RawResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RawResponse {
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(self, content_type: &str, body: Vec<u8>) -> Self {
+        let mut response = self.header("Content-Type", content_type);
+        response.body = body;
+        response
+    }
+
+    /// Ligne de statut et en-têtes, suivis du cookie de session et de la ligne vide.
+    /// Content-Length est ajouté sauf s'il est fourni ou si le corps est envoyé en chunked.
+    pub fn head(&self, cookie: &str) -> String {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\n",
+            self.status,
+            Response::status_text(self.status)
+        );
+        for (name, value) in &self.headers {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+        if !self
+            .headers
+            .iter()
+            .any(|(name, _)| name == "Content-Length" || name == "Transfer-Encoding")
+        {
+            head += &format!("Content-Length: {}\r\n", self.body.len());
+        }
+        head += cookie;
+        head += "\r\n";
+        head
+    }
+}
+
+impl Server {
+    /// Envoie une `RawResponse`; le corps est omis pour HEAD.
+    pub fn send_raw_response(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        config: &Config,
+        cookie: &String,
+        response: RawResponse,
+    ) -> Option<Transfer> {
+        let mut bytes = response.head(cookie).into_bytes();
+        if request.method != "HEAD" {
+            bytes.extend_from_slice(&response.body);
+        }
+
+        self.access_log(request, config, response.status, cookie);
+        match Transfer::File(FileTransfer::from_bytes(bytes)).start(stream) {
+            Ok(transfer) => transfer,
+            Err(e) => {
+                Self::error_log(
+                    request,
+                    config,
+                    "send_raw_response",
+                    file!(),
+                    line!(),
+                    ServerError::IOError(&e),
+                );
+                None
+            }
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
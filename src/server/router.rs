@@ -1,26 +1,52 @@
-use crate::{Config, ServerError};
+use crate::{Config, ServerError, SessionExpiry};
 
-use super::Request;
+use super::{
+    session_store, FastCgiPool, LockStore, Request, ResponseCache, SessionStore, StaticCache,
+    Transfer, TusStore, UploadUsage, UpstreamPools,
+};
 pub use super::{Server, Session};
+use chrono::DateTime;
 use mio::net::{TcpListener, TcpStream};
 use mio::{Events, Interest, Poll, Token};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, ErrorKind};
 use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 // -------------------------------------------------------------------------------------
 // ROUTER
 // -------------------------------------------------------------------------------------
 const CLIENT_START: Token = Token(1000); // Token de départ pour les clients
 
+/// État conservé d'une requête à l'autre et partagé par tous les serveurs du Router.
+#[derive(Debug, Default)]
+pub struct ServerState {
+    pub static_cache: StaticCache,
+    pub locks: HashMap<PathBuf, LockStore>, // verrous WebDAV, par dossier racine
+    pub uploads: TusStore,
+    pub upload_usage: UploadUsage,
+    pub upstreams: UpstreamPools,
+    pub response_cache: ResponseCache,
+    pub sessions: Box<dyn SessionStore>, // données des sessions, par identifiant
+    pub regenerated_sessions: Vec<(String, String)>, // (ancien, nouvel) identifiant
+}
+
 #[derive(Debug)]
 pub struct Router {
     pub servers: Vec<Server>,
-    pub sessions: HashMap<Token, Session>,
+    pub sessions: HashMap<String, Session>, // Sessions par identifiant
     pub listeners: HashMap<Token, TcpListener>, // Associe un token à un TcpListener
     pub clients: HashMap<Token, TcpStream>,     // Associe un token à un TcpStream
+    pub transfers: HashMap<Token, Transfer>, // Réponses en cours d'envoi par client
+    pub pending_reads: HashSet<Token>,       // Clients ayant envoyé des données pendant un envoi
+    pub cgi_pipes: HashMap<Token, Token>, // Associe un pipe CGI (ou un socket SCGI/uwsgi/proxy) à son client
+    pub fastcgi: FastCgiPool,                // Connexions vers les applications FastCGI
+    pub state: ServerState,
     pub next_token: usize,
     pub request_queue: Vec<Request>,
+    pub pending_bodies: HashMap<Token, Request>, // Requêtes dont le corps arrive encore, par client
+    pub next_sweep: Option<Instant>, // Prochaine purge des sessions expirées
 }
 
 impl Router {
@@ -30,8 +56,15 @@ impl Router {
             sessions: HashMap::new(),
             listeners: HashMap::new(),
             clients: HashMap::new(),
+            transfers: HashMap::new(),
+            pending_reads: HashSet::new(),
+            cgi_pipes: HashMap::new(),
+            fastcgi: FastCgiPool::default(),
+            state: ServerState::default(),
             next_token: CLIENT_START.0,
             request_queue: vec![],
+            pending_bodies: HashMap::new(),
+            next_sweep: None,
         }
     }
 
@@ -82,6 +115,12 @@ impl Router {
     pub fn run(&mut self, config: &Config) -> io::Result<()> {
         let mut poll = Poll::new()?;
         let mut server_tokens = HashMap::new();
+        self.state.static_cache = StaticCache::new(&config.http.static_cache);
+        self.state.upstreams = UpstreamPools::new(&config.http.upstreams);
+        self.state.sessions = session_store(&config.http.session_store);
+        self.next_sweep = Some(config.http.session_store.sweep_interval)
+            .filter(|interval| *interval > 0)
+            .map(|interval| Instant::now() + Duration::from_secs(interval));
 
         // Enregistrer chaque listener avec un token unique
         for (token, listener) in &mut self.listeners {
@@ -93,12 +132,40 @@ impl Router {
         let mut events = Events::with_capacity(config.log_files.events_limit);
 
         loop {
-            poll.poll(&mut events, None)?;
+            poll.poll(&mut events, self.poll_timeout())?;
+            self.expire_transfers(&poll, config)?;
+            self.run_health_checks(&poll, config);
+            self.sweep_sessions(config);
 
             for event in events.iter() {
+                if let Some(&client_token) = self.cgi_pipes.get(&event.token()) {
+                    // Sortie disponible (ou stdin prêt) pour un script CGI, une application SCGI
+                    // ou un serveur amont
+                    self.continue_transfer(client_token, &poll, config)?;
+                    continue;
+                }
+                if self.state.upstreams.contains(event.token()) {
+                    // Réponse (ou envoi possible) d'un test de santé
+                    self.state.upstreams.ready(event.token(), poll.registry());
+                    self.state.upstreams.log_errors(config);
+                    continue;
+                }
+                if self.fastcgi.contains(event.token()) {
+                    // Enregistrements reçus (ou envoi possible) sur une connexion FastCGI
+                    let clients =
+                        self.fastcgi
+                            .ready(event.token(), poll.registry(), &mut self.transfers);
+                    for client_token in clients {
+                        self.continue_transfer(client_token, &poll, config)?;
+                    }
+                    continue;
+                }
 
                 if event.is_error() || event.is_read_closed() {
                     // Nettoyer les tokens inactifs
+                    self.remove_transfer(event.token());
+                    self.pending_reads.remove(&event.token());
+                    self.pending_bodies.remove(&event.token());
                     if let Some(mut stream) = self.clients.remove(&event.token()) {
                         poll.registry().deregister(&mut stream)?;
                         let addr = stream.peer_addr()?;
@@ -125,91 +192,179 @@ impl Router {
                     self.accept_connection(event.token(), &poll)?;
                     // println!("Nouvelle connexion sur le port {}", addr.port());
                 } else {
-                    // Données reçues sur un TcpStream
-                    let stream = (self.clients.get_mut(&event.token()))
-                        .expect("Erreur lors de la recupération du canal tcpstream");
-                    let req = Request::read_request(stream);
-                    let mut cookie = req.id_session.clone();
-                    // println!("cookie extract: {}",cookie);
-                    let client_token = Token(self.next_token);
-                    self.next_token += 1;
-                    // Tentative de récupération du cookie
-                    if !cookie.is_empty() {
-                        // Recherche d'une session existante avec le même cookie
-                        let mut session_found = false;
-
-                        for (old_token, session) in self.sessions.clone().iter() {
-                            if session.id.trim() == cookie && !session.is_expired() {
-                                let mut new_session = Session::new();
-                                new_session.id = session.id.clone();
-                                self.sessions.remove(&old_token);
-                                self.sessions.insert(client_token.clone(), new_session);
-                                session_found = true;
-                                break;
-                            }
-                        }
+                    if event.is_writable() && self.transfers.contains_key(&event.token()) {
+                        // Le client peut recevoir la suite d'un fichier en cours d'envoi
+                        self.continue_transfer(event.token(), &poll, config)?;
+                    }
+                    if !event.is_readable() || !self.clients.contains_key(&event.token()) {
+                        continue;
+                    }
+                    if self.transfers.contains_key(&event.token()) {
+                        // Requête suivante (keep-alive, pipelining) reçue pendant l'envoi: les
+                        // événements ne sont signalés qu'une fois, elle sera lue à la fin de l'envoi
+                        self.pending_reads.insert(event.token());
+                        continue;
+                    }
+                    self.read_client(event.token(), &poll, config)?;
+                }
+            }
+        }
+    }
 
-                        if !session_found {
-                            // Si aucune session existante n'est trouvée, créez une nouvelle session
-                            let new_session = Session::new();
-                            self.sessions
-                                .insert(client_token.clone(), new_session.clone());
-                        }
-                    } else {
-                        // Si aucun cookie n'est trouvé, créez une nouvelle session
-                        let new_session = Session::new();
-                        self.sessions
-                            .insert(client_token.clone(), new_session.clone());
+    /// Lit les données reçues d'un client et traite la requête complète.
+    fn read_client(&mut self, token: Token, poll: &Poll, config: &Config) -> io::Result<()> {
+        self.rename_sessions();
+        // Données reçues sur un TcpStream
+        let stream = (self.clients.get_mut(&token))
+            .expect("Erreur lors de la recupération du canal tcpstream");
+        let mut req = Request::read_request(stream);
+        // Cookie de session du serveur destinataire
+        let session_cookie = self
+            .servers
+            .iter()
+            .find(|server| {
+                server.ip_addr == req.host && server.ports.contains(&req.port)
+            })
+            .map(|server| server.session_cookie.clone())
+            .unwrap_or_default();
+        let value = req.cookies.get(&session_cookie.name);
+        let mut cookie = value
+            .and_then(|value| session_cookie.verify(value))
+            .unwrap_or_default();
+        if value.is_some() && cookie.is_empty() {
+            // Cookie falsifié ou malformé: une nouvelle session est créée
+            Server::error_log(
+                &req,
+                config,
+                "session_cookie",
+                file!(),
+                line!(),
+                ServerError::IOError(&io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Cookie de session invalide",
+                )),
+            );
+        }
+        // Recherche de la session du cookie
+        let expiry = config.http.session_store.expiry;
+        match self.sessions.get_mut(&cookie) {
+            Some(session) if !session.is_expired() => {
+                if expiry == SessionExpiry::Sliding {
+                    session.refresh();
+                }
+            }
+            _ => {
+                // Session inconnue ou expirée: nouvelle session, ou reprise de celle
+                // gardée par le store (après un redémarrage)
+                self.sessions.remove(&cookie);
+                let lifetime = session_cookie.lifetime as i64 * 1000;
+                let mut new_session = Session::with_lifetime(lifetime);
+                let stored = match cookie.is_empty() {
+                    true => None,
+                    false => self.state.sessions.load(&cookie),
+                };
+                if let Some(data) = stored {
+                    new_session.id = cookie.clone();
+                    if expiry == SessionExpiry::Absolute {
+                        new_session.validity_time =
+                            DateTime::from_timestamp(data.expires_at, 0)
+                                .unwrap_or(new_session.validity_time);
                     }
+                }
+                cookie = new_session.id.clone();
+                self.sessions.insert(cookie.clone(), new_session);
+            }
+        }
 
-                    if let Some(session) = self.sessions.get_mut(&client_token) {
-                        cookie = Session::make_cookie(
-                            "cookie_01",
-                            &*session.id,
-                            session.expiration_time,
+        if let Some(session) = self.sessions.get(&cookie) {
+            let value = session_cookie.sign(&session.id);
+            cookie = Session::make_cookie(&session_cookie, &value, session.remaining());
+            // Identifiant de la session retenue (quotas d'envoi...)
+            req.id_session = session.id.clone();
+            // Données de la session, prolongées avec elle
+            if let Some(mut data) = self.state.sessions.load(&session.id) {
+                let expires_at = session.validity_time.timestamp();
+                if data.expires_at != expires_at {
+                    data.expires_at = expires_at;
+                    if let Err(e) = self.state.sessions.save(&session.id, &data) {
+                        Server::error_log(
+                            &req,
+                            config,
+                            "session_store",
+                            file!(),
+                            line!(),
+                            ServerError::IOError(&e),
                         );
                     }
+                }
+                req.session = data.values;
+            }
+        }
 
-                    if req.method == "GET" || req.method == "POST" {
-                        self.request_queue.push(req);
-                    } else {
-                        for (i, waiting_req) in self.request_queue.clone().iter().enumerate() {
-                            if waiting_req.method == "POST" {
-                                if let Some(content_length) = waiting_req.content_length {
-                                    println!("content-length: {} <======> body len: {}", content_length, req.body.len());
-                                    if content_length > waiting_req.body.len()  {
-                                        if let Some(boundary) = waiting_req.boundary.clone() {
-                                            if req.body.contains(&boundary) {
-                                                self.request_queue[i].body.push_str(&req.body);
-                                                self.request_queue[i]
-                                                    .body_byte
-                                                    .extend_from_slice(&req.body_byte);
-                                                if let Some(content_length) =
-                                                    self.request_queue[i].content_length
-                                                {
-                                                    if self.request_queue[i].body.len()
-                                                        >= content_length
-                                                    {
-                                                        self.request_queue[i].complete = true;
-                                                    }
-                                                }
-                                            }
+        if Request::is_method(&req.method) {
+            if !req.complete && (req.method != "POST" || req.boundary.is_none()) {
+                // Corps brut (PUT, PATCH, POST d'un script CGI...) attendu sur la même connexion
+                self.pending_bodies.insert(token, req);
+            } else {
+                self.request_queue.push(req);
+            }
+        } else if let Some(waiting) = self.pending_bodies.get_mut(&token) {
+            // Suite du corps brut envoyé par ce client: compté en octets
+            waiting.body_byte.extend_from_slice(&req.body_byte);
+            if waiting.method == "POST" {
+                // body_byte d'un POST garde la section d'en-têtes
+                waiting.body.push_str(&req.body);
+            } else {
+                waiting.body = String::from_utf8_lossy(&waiting.body_byte).to_string();
+                waiting.length = waiting.body_byte.len();
+            }
+            waiting.complete = waiting.raw_body().len() >= waiting.content_length.unwrap_or(0);
+            if waiting.complete {
+                if let Some(waiting) = self.pending_bodies.remove(&token) {
+                    self.request_queue.push(waiting);
+                }
+            }
+        } else {
+            for (i, waiting_req) in self.request_queue.clone().iter().enumerate() {
+                if waiting_req.method == "POST" {
+                    if let Some(content_length) = waiting_req.content_length {
+                        println!("content-length: {} <======> body len: {}", content_length, req.body.len());
+                        if content_length > waiting_req.body.len()  {
+                            if let Some(boundary) = waiting_req.boundary.clone() {
+                                if req.body.contains(&boundary) {
+                                    self.request_queue[i].body.push_str(&req.body);
+                                    self.request_queue[i]
+                                        .body_byte
+                                        .extend_from_slice(&req.body_byte);
+                                    if let Some(content_length) =
+                                        self.request_queue[i].content_length
+                                    {
+                                        if self.request_queue[i].body.len()
+                                            >= content_length
+                                        {
+                                            self.request_queue[i].complete = true;
                                         }
                                     }
                                 }
                             }
                         }
                     }
-                    Self::route_request(
-                        &mut self.request_queue,
-                        self.servers.clone(),
-                        stream,
-                        cookie,
-                        &config,
-                    );
                 }
             }
         }
+        let transfer = Self::route_request(
+            &mut self.request_queue,
+            self.servers.clone(),
+            stream,
+            cookie,
+            config,
+            &mut self.state,
+        );
+
+        if let Some(transfer) = transfer {
+            self.start_transfer(token, transfer, poll)?;
+        }
+        self.start_revalidations(poll, config)
     }
 
     /// Accepte une nouvelle connexion et l'ajoute à la liste des clients.
@@ -225,31 +380,272 @@ impl Router {
         Ok(())
     }
 
+    /// Poursuit l'envoi d'une réponse lorsque le client redevient disponible en écriture.
+    fn continue_transfer(&mut self, token: Token, poll: &Poll, config: &Config) -> io::Result<()> {
+        if !self.clients.contains_key(&token) {
+            return self.continue_revalidation(token, config);
+        }
+        let (Some(stream), Some(transfer)) =
+            (self.clients.get_mut(&token), self.transfers.get_mut(&token))
+        else {
+            return Ok(());
+        };
+
+        let result = transfer.advance(stream);
+        if let Some(response) = transfer.cgi_response() {
+            // Lignes de stderr reçues entre-temps
+            response.log_errors(config);
+        }
+        if let Transfer::Proxy(proxy) = transfer {
+            // Connexions et échecs des serveurs amont
+            self.state.upstreams.report(proxy.take_reports());
+            self.state.upstreams.log_errors(config);
+        }
+        match result {
+            Ok(false) => return Ok(()),
+            Ok(true) => {
+                let mut finished = self.remove_transfer(token);
+                let Some(stream) = self.clients.get_mut(&token) else {
+                    return Ok(());
+                };
+                let next = match finished.as_mut().and_then(Transfer::cgi_response) {
+                    Some(response) => {
+                        let server = response.server.clone();
+                        server.finish_cgi(stream, response, config, &mut self.state)
+                    }
+                    None => None,
+                };
+                match next {
+                    // Redirection locale vers une autre ressource encore en cours d'envoi
+                    Some(next) => self.start_transfer(token, next, poll)?,
+                    None => {
+                        poll.registry()
+                            .reregister(stream, token, Interest::READABLE)?;
+                        if self.pending_reads.remove(&token) {
+                            // Requête arrivée pendant l'envoi
+                            return self.read_client(token, poll, config);
+                        }
+                    }
+                }
+                return self.start_revalidations(poll, config);
+            }
+            Err(e) => {
+                let mut err_req = Request::default();
+                err_req.host = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                Server::error_log(
+                    &err_req,
+                    config,
+                    "continue_transfer",
+                    file!(),
+                    line!(),
+                    ServerError::IOError(&e),
+                );
+                self.pending_reads.remove(&token);
+                self.pending_bodies.remove(&token);
+                if let Some(mut stream) = self.clients.remove(&token) {
+                    poll.registry().deregister(&mut stream)?;
+                    let _ = stream.shutdown(std::net::Shutdown::Both);
+                }
+            }
+        }
+        self.remove_transfer(token);
+        Ok(())
+    }
+
+    /// Poursuit une revalidation du cache, sans client: la réponse complète remplace
+    /// l'entrée périmée.
+    fn continue_revalidation(&mut self, token: Token, config: &Config) -> io::Result<()> {
+        let Some(transfer) = self.transfers.get_mut(&token) else {
+            return Ok(());
+        };
+        let result = transfer.advance_detached();
+        if let Transfer::Proxy(proxy) = transfer {
+            self.state.upstreams.report(proxy.take_reports());
+            self.state.upstreams.log_errors(config);
+        }
+        if let Ok(false) = result {
+            return Ok(());
+        }
+        if let Some(response) = self.remove_transfer(token).as_mut().and_then(Transfer::cgi_response) {
+            response.log_errors(config);
+            if let Some(capture) = response.capture.take() {
+                self.state.response_cache.store(capture, &response.request);
+            }
+        }
+        Ok(())
+    }
+
+    /// Lance les revalidations du cache demandées pendant le traitement des requêtes,
+    /// chacune sous un token sans client.
+    fn start_revalidations(&mut self, poll: &Poll, config: &Config) -> io::Result<()> {
+        for transfer in self.state.response_cache.take_background() {
+            let token = Token(self.next_token);
+            self.next_token += 1;
+            self.start_transfer(token, transfer, poll)?;
+            // Premier tour: une requête déjà en échec n'attend aucun événement
+            self.continue_revalidation(token, config)?;
+        }
+        Ok(())
+    }
+
+    /// Garde une réponse dont l'envoi continuera sur les événements WRITABLE du client.
+    /// Les pipes d'un processus CGI et les connexions FastCGI, SCGI, uwsgi ou proxy sont enregistrés
+    /// dans le même Poll.
+    fn start_transfer(&mut self, token: Token, mut transfer: Transfer, poll: &Poll) -> io::Result<()> {
+        let next_token = &mut self.next_token;
+        let allocate = || {
+            *next_token += 1;
+            Token(*next_token - 1)
+        };
+        match &mut transfer {
+            Transfer::Cgi(cgi) => {
+                for pipe in cgi.register(poll.registry(), allocate)? {
+                    self.cgi_pipes.insert(pipe, token);
+                }
+            }
+            Transfer::Scgi(scgi) => {
+                for socket in scgi.register(poll.registry(), allocate)? {
+                    self.cgi_pipes.insert(socket, token);
+                }
+            }
+            Transfer::Proxy(proxy) => {
+                for socket in proxy.register(poll.registry(), allocate)? {
+                    self.cgi_pipes.insert(socket, token);
+                }
+            }
+            Transfer::FastCgi(fastcgi) => {
+                if let Err(e) = self.fastcgi.attach(poll.registry(), allocate, token, fastcgi) {
+                    // Application injoignable: 502 Bad Gateway
+                    fastcgi.response.fail(502, &e.to_string());
+                }
+            }
+            _ => {}
+        }
+        if let Some(stream) = self.clients.get_mut(&token) {
+            // Attendre que le client soit prêt à recevoir la suite
+            poll.registry()
+                .reregister(stream, token, Interest::READABLE | Interest::WRITABLE)?;
+        }
+        self.transfers.insert(token, transfer);
+        Ok(())
+    }
+
+    /// Temps restant avant l'échéance du prochain script CGI, serveur amont ou test de
+    /// santé, `None` s'il n'y en a pas.
+    fn poll_timeout(&self) -> Option<Duration> {
+        self.transfers
+            .values()
+            .filter_map(Transfer::deadline)
+            .chain(self.state.upstreams.deadline())
+            .chain(self.next_sweep)
+            .min()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Arrête les scripts CGI et les requêtes proxy ayant dépassé leur délai et envoie
+    /// la réponse d'échec.
+    fn expire_transfers(&mut self, poll: &Poll, config: &Config) -> io::Result<()> {
+        let now = Instant::now();
+        let expired: Vec<Token> = self
+            .transfers
+            .iter_mut()
+            .filter(|(_, transfer)| transfer.deadline().is_some_and(|deadline| deadline <= now))
+            .map(|(token, transfer)| {
+                transfer.expire();
+                *token
+            })
+            .collect();
+        for token in expired {
+            self.continue_transfer(token, poll, config)?;
+        }
+        Ok(())
+    }
+
+    /// Lance les tests de santé des groupes de serveurs amont arrivés à échéance.
+    fn run_health_checks(&mut self, poll: &Poll, config: &Config) {
+        let next_token = &mut self.next_token;
+        let allocate = || {
+            *next_token += 1;
+            Token(*next_token - 1)
+        };
+        self.state
+            .upstreams
+            .run_health_checks(poll.registry(), allocate);
+        self.state.upstreams.log_errors(config);
+    }
+
+    /// Renomme les sessions dont l'identifiant a été régénéré (changement de privilèges):
+    /// l'ancien identifiant n'est plus accepté.
+    fn rename_sessions(&mut self) {
+        for (old_id, new_id) in self.state.regenerated_sessions.drain(..) {
+            if let Some(mut session) = self.sessions.remove(&old_id) {
+                session.id = new_id.clone();
+                self.sessions.insert(new_id, session);
+            }
+        }
+    }
+
+    /// Oublie les sessions expirées, du Router comme du store, toutes les `sweep_interval`
+    /// secondes.
+    fn sweep_sessions(&mut self, config: &Config) {
+        let Some(next_sweep) = self.next_sweep else {
+            return;
+        };
+        if Instant::now() < next_sweep {
+            return;
+        }
+        self.sessions.retain(|_, session| !session.is_expired());
+        if let Err(e) = self.state.sessions.sweep() {
+            Server::error_log(
+                &Request::default(),
+                config,
+                "sweep_sessions",
+                file!(),
+                line!(),
+                ServerError::IOError(&e),
+            );
+        }
+        let interval = Duration::from_secs(config.http.session_store.sweep_interval);
+        self.next_sweep = Some(Instant::now() + interval);
+    }
+
+    /// Retire la réponse en cours d'un client et oublie les pipes CGI associés
+    /// (le processus est arrêté à la destruction du transfert).
+    /// Une requête FastCGI inachevée est abandonnée auprès de l'application, et le serveur
+    /// amont d'une requête proxy est libéré.
+    fn remove_transfer(&mut self, token: Token) -> Option<Transfer> {
+        self.cgi_pipes.retain(|_, client| *client != token);
+        let mut transfer = self.transfers.remove(&token);
+        match &mut transfer {
+            Some(Transfer::FastCgi(fastcgi)) => self.fastcgi.abort(fastcgi),
+            Some(Transfer::Proxy(proxy)) => self.state.upstreams.report(proxy.finish()),
+            _ => {}
+        }
+        transfer
+    }
+
     // Route une requête HTTP et génère une réponse.
+    // Une seule requête par appel: un transfert occupe le flux jusqu'à la fin de l'envoi
     pub fn route_request(
         request_queue: &mut Vec<Request>,
         servers: Vec<Server>,
         stream: &mut TcpStream,
         cookie: String,
         config: &Config,
-    ) {
+        state: &mut ServerState,
+    ) -> Option<Transfer> {
         // On récupère le hostname, l'adresse ip et le port de la requête
         // On parcoure la liste des serveurs et on vérifie lequel a le hostname, le port et l'ip correspondant
-        for (i, req) in request_queue.clone().into_iter().enumerate() {
-            for server in servers.iter() {
-                if server.ip_addr == req.host && server.ports.contains(&req.port) {
-                    if req.method == "GET" {
-                        server.handle_request(stream, req.clone(), cookie.clone(), config);
-                        request_queue.remove(i);
-                        break;
-                    } else if req.complete {
-                        println!("arret possible");
-                        server.handle_request(stream, req.clone(), cookie.clone(), config);
-                        request_queue.remove(i);
-                        break;
-                    }
-                }
-            }
-        }
+        let destination = |req: &Request| {
+            servers
+                .iter()
+                .position(|server| server.ip_addr == req.host && server.ports.contains(&req.port))
+        };
+        let i = request_queue
+            .iter()
+            .position(|req| (req.method == "GET" || req.complete) && destination(req).is_some())?;
+        let req = request_queue.remove(i);
+        let server = &servers[destination(&req)?];
+        server.handle_request(stream, req, cookie, config, state)
     }
 }
@@ -0,0 +1,260 @@
+use mio::net::TcpStream;
+use mio::{Registry, Token};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use super::{
+    CgiResponse, CgiScript, GatewayLocation, Request, Server, Transfer, UpstreamSocket, CGI,
+};
+
+// -------------------------------------------------------------------------------------
+// SCGI / UWSGI
+// -------------------------------------------------------------------------------------
+// Taille maximale lue sur le socket à chaque tour
+const SCGI_READ_SIZE: usize = 64 * 1024;
+
+/// Protocole parlé avec l'application: une connexion par requête, l'environnement CGI
+/// en en-tête puis le corps; la réponse est lue jusqu'à la fermeture du socket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScgiProtocol {
+    /// Netstring de paires "NOM\0valeur\0" (CONTENT_LENGTH en premier)
+    Scgi,
+    /// Paquet binaire uwsgi: en-tête de 4 octets puis paires préfixées par leur longueur
+    Uwsgi,
+}
+
+impl ScgiProtocol {
+    /// En-tête de requête portant les variables d'environnement.
+    pub fn encode(
+        &self,
+        env: &HashMap<String, String>,
+        content_length: usize,
+    ) -> io::Result<Vec<u8>> {
+        // CONTENT_LENGTH ouvre obligatoirement l'en-tête SCGI; le reste dans un ordre stable
+        let content_length = content_length.to_string();
+        let mut others: Vec<(&str, &str)> = env
+            .iter()
+            .filter(|(name, _)| *name != "CONTENT_LENGTH")
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        others.sort();
+        let mut vars = vec![("CONTENT_LENGTH", content_length.as_str())];
+        vars.extend(others);
+
+        match self {
+            ScgiProtocol::Scgi => {
+                let mut headers = vec![];
+                for (name, value) in vars {
+                    headers.extend_from_slice(name.as_bytes());
+                    headers.push(0);
+                    headers.extend_from_slice(value.as_bytes());
+                    headers.push(0);
+                }
+                headers.extend_from_slice(b"SCGI\x001\x00");
+
+                let mut bytes = format!("{}:", headers.len()).into_bytes();
+                bytes.extend(headers);
+                bytes.push(b',');
+                Ok(bytes)
+            }
+            ScgiProtocol::Uwsgi => {
+                let mut vars_bytes = vec![];
+                for (name, value) in vars {
+                    for part in [name, value] {
+                        let length = u16::try_from(part.len()).map_err(|_| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                "variable uwsgi trop longue",
+                            )
+                        })?;
+                        vars_bytes.extend_from_slice(&length.to_le_bytes());
+                        vars_bytes.extend_from_slice(part.as_bytes());
+                    }
+                }
+                let size = u16::try_from(vars_bytes.len()).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "en-tête uwsgi trop long")
+                })?;
+
+                // modifier1 = 0 (WSGI), taille little-endian, modifier2 = 0
+                let mut bytes = vec![0];
+                bytes.extend_from_slice(&size.to_le_bytes());
+                bytes.push(0);
+                bytes.extend(vars_bytes);
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+/// Requête en cours vers une application SCGI ou uwsgi, sur sa propre connexion.
+#[derive(Debug)]
+pub struct ScgiTransfer {
+    socket: Option<UpstreamSocket>,
+    // En-tête et corps de la requête, et position déjà écrite
+    outgoing: Vec<u8>,
+    written: usize,
+    pub response: CgiResponse,
+}
+
+impl ScgiTransfer {
+    /// Enregistre le socket dans le Poll du Router. Renvoie le token utilisé.
+    pub fn register(
+        &mut self,
+        registry: &Registry,
+        next_token: impl FnOnce() -> Token,
+    ) -> io::Result<Vec<Token>> {
+        let Some(socket) = &mut self.socket else {
+            return Ok(vec![]);
+        };
+        let token = next_token();
+        socket.register(registry, token)?;
+        Ok(vec![token])
+    }
+
+    /// Envoie la requête à l'application et relaie sa réponse au client, sans bloquer.
+    /// Renvoie `Ok(true)` lorsque la réponse est terminée.
+    pub fn advance<W: Write>(&mut self, stream: &mut W) -> io::Result<bool> {
+        self.write_request();
+        loop {
+            if !self.response.flush(stream)? {
+                return Ok(false);
+            }
+            if self.response.finished {
+                return Ok(true);
+            }
+            if !self.read_response() {
+                return Ok(false);
+            }
+        }
+    }
+
+    fn write_request(&mut self) {
+        let Some(socket) = &mut self.socket else {
+            return;
+        };
+        while self.written < self.outgoing.len() {
+            match socket.write(&self.outgoing[self.written..]) {
+                Ok(n) => self.written += n,
+                // Connexion TCP encore en cours d'établissement
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::NotConnected =>
+                {
+                    return
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.socket = None;
+                    self.response.fail(502, &e.to_string());
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Lit un morceau de la réponse. Renvoie `false` s'il n'y a rien à lire pour l'instant.
+    fn read_response(&mut self) -> bool {
+        let Some(socket) = &mut self.socket else {
+            return false;
+        };
+        let mut buffer = vec![0; SCGI_READ_SIZE];
+        match socket.read(&mut buffer) {
+            Ok(0) => {
+                // L'application ferme la connexion en fin de réponse
+                self.socket = None;
+                self.response.end();
+            }
+            Ok(read) => self.response.feed(&buffer[..read]),
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock
+                    || e.kind() == io::ErrorKind::NotConnected =>
+            {
+                return false
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                self.socket = None;
+                self.response.fail(502, &e.to_string());
+            }
+        }
+        true
+    }
+}
+
+impl Server {
+    /// Ouvre une connexion vers l'application SCGI ou uwsgi et lui transmet la requête;
+    /// la réponse est relayée par le Router au fil des événements sur le socket.
+    pub fn handle_scgi(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        script: &CgiScript,
+        gateway: &GatewayLocation,
+        protocol: ScgiProtocol,
+        cookie: &str,
+    ) -> Option<Transfer> {
+        let remote_addr = stream
+            .peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+        let env = CGI::environment(self, request, script, &remote_addr);
+        let body = request.raw_body();
+        let mut response = CgiResponse::new(self.clone(), request.clone(), &script.path, cookie);
+
+        let socket = protocol.encode(&env, body.len()).and_then(|mut outgoing| {
+            outgoing.extend_from_slice(body);
+            Ok((UpstreamSocket::connect(&gateway.target)?, outgoing))
+        });
+        let transfer = match socket {
+            Ok((socket, outgoing)) => ScgiTransfer {
+                socket: Some(socket),
+                outgoing,
+                written: 0,
+                response,
+            },
+            Err(e) => {
+                // Application injoignable: 502 Bad Gateway
+                response.fail(502, &e.to_string());
+                ScgiTransfer {
+                    socket: None,
+                    outgoing: vec![],
+                    written: 0,
+                    response,
+                }
+            }
+        };
+        Some(Transfer::Scgi(Box::new(transfer)))
+    }
+}
+// -------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scgi_headers() {
+        let env = HashMap::from([
+            ("REQUEST_METHOD".to_string(), "POST".to_string()),
+            ("CONTENT_LENGTH".to_string(), "99".to_string()),
+        ]);
+        let bytes = ScgiProtocol::Scgi.encode(&env, 5).unwrap();
+        let expected = b"CONTENT_LENGTH\x005\x00REQUEST_METHOD\x00POST\x00SCGI\x001\x00";
+        assert_eq!(
+            bytes,
+            [format!("{}:", expected.len()).as_bytes(), expected, b","].concat()
+        );
+    }
+
+    #[test]
+    fn test_uwsgi_packet() {
+        let env = HashMap::from([("A".to_string(), "bc".to_string())]);
+        let bytes = ScgiProtocol::Uwsgi.encode(&env, 0).unwrap();
+        let vars = b"\x0e\x00CONTENT_LENGTH\x01\x000\x01\x00A\x02\x00bc";
+        assert_eq!(&bytes[..4], &[0, vars.len() as u8, 0, 0]);
+        assert_eq!(&bytes[4..], vars);
+
+        let env = HashMap::from([("A".to_string(), "x".repeat(70000))]);
+        assert!(ScgiProtocol::Uwsgi.encode(&env, 0).is_err());
+    }
+}
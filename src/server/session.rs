@@ -1,9 +1,17 @@
 use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
 use mio::net::TcpStream;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use std::collections::HashMap;
-use std::io::{BufReader, Read};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+use crate::{SessionStorage, SessionStoreConfig};
+
 // -------------------------------------------------------------------------------------
 // SESSION
 // -------------------------------------------------------------------------------------
@@ -18,20 +26,34 @@ impl Session {
     pub(crate) const SESSION_LIFETIME: i64 = 60 * 60 * 1000;
 
     pub fn new() -> Self {
-        let expires_duration = Duration::milliseconds(Self::SESSION_LIFETIME);
+        Self::with_lifetime(Self::SESSION_LIFETIME)
+    }
+
+    /// Nouvelle session valable `lifetime` millisecondes.
+    pub fn with_lifetime(lifetime: i64) -> Self {
+        let expires_duration = Duration::milliseconds(lifetime);
 
         Self {
             id: Uuid::new_v4().into(),
-            expiration_time: Self::SESSION_LIFETIME,
+            expiration_time: lifetime,
             validity_time: Utc::now() + expires_duration,
         }
     }
 
     pub fn is_expired(&self) -> bool {
-        println!("verification {}   != {}", Utc::now(), self.validity_time);
         Utc::now() > self.validity_time
     }
 
+    /// Repousse l'expiration d'une durée de vie complète (expiration glissante).
+    pub fn refresh(&mut self) {
+        self.validity_time = Utc::now() + Duration::milliseconds(self.expiration_time);
+    }
+
+    /// Millisecondes restantes avant l'expiration (Expires du cookie).
+    pub fn remaining(&self) -> i64 {
+        (self.validity_time - Utc::now()).num_milliseconds().max(0)
+    }
+
     /// Récupère la valeur d'un cookie spécifique à partir d'un TcpStream.
     pub fn get_cookie_from_stream(stream: &mut TcpStream, cookie_name: &str) -> Option<String> {
         // Crée un BufReader pour lire les données du flux
@@ -89,7 +111,7 @@ impl Session {
         None // Cookie non trouvé
     }
 
-    pub fn make_cookie(cookie_name: &str, cookie_value: &str, expires_in: i64) -> String {
+    pub fn make_cookie(cookie: &SessionCookie, cookie_value: &str, expires_in: i64) -> String {
         // Convertir expires_in (millisecondes) en Duration
         let expires_duration = Duration::milliseconds(expires_in);
 
@@ -99,12 +121,331 @@ impl Session {
             .to_string();
 
         // Construire l'en-tête Set-Cookie
-        let cookie_header = format!(
-            "Set-Cookie: {}={}; Path=/; HttpOnly; Expires={}\r\n",
-            cookie_name, cookie_value, expires
-        );
+        let mut cookie_header = format!("Set-Cookie: {}={}", cookie.name, cookie_value);
+        if !cookie.path.is_empty() {
+            cookie_header += &format!("; Path={}", cookie.path);
+        }
+        if !cookie.domain.is_empty() {
+            cookie_header += &format!("; Domain={}", cookie.domain);
+        }
+        cookie_header += &format!("; Expires={}", expires);
+        if cookie.max_age {
+            cookie_header += &format!("; Max-Age={}", (expires_in + 500) / 1000);
+        }
+        // SameSite=None n'est accepté par les navigateurs qu'avec Secure
+        if cookie.secure || cookie.same_site == Some(SameSite::None) {
+            cookie_header += "; Secure";
+        }
+        if cookie.http_only {
+            cookie_header += "; HttpOnly";
+        }
+        if let Some(same_site) = cookie.same_site {
+            cookie_header += &format!("; SameSite={:?}", same_site);
+        }
+
+        cookie_header + "\r\n"
+    }
+}
+// -------------------------------------------------------------------------------------
+
+// -------------------------------------------------------------------------------------
+// SESSION COOKIE
+// -------------------------------------------------------------------------------------
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+pub fn default_cookie_name() -> String {
+    "cookie_01".to_string()
+}
+
+pub fn default_cookie_path() -> String {
+    "/".to_string()
+}
+
+pub fn default_http_only() -> bool {
+    true
+}
+
+pub fn default_session_lifetime() -> u64 {
+    (Session::SESSION_LIFETIME / 1000) as u64
+}
+
+/// Cookie portant l'identifiant de session pour un serveur.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SessionCookie {
+    #[serde(default = "default_cookie_name")]
+    pub name: String,
+    #[serde(default = "default_cookie_path")]
+    pub path: String,
+    #[serde(default)]
+    pub domain: String, // vide = hôte de la requête seulement
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default = "default_http_only")]
+    pub http_only: bool,
+    #[serde(default)]
+    pub same_site: Option<SameSite>, // "Strict", "Lax" ou "None"
+    #[serde(default)]
+    pub max_age: bool, // Max-Age en plus d'Expires
+    #[serde(default = "default_session_lifetime")]
+    pub lifetime: u64, // secondes
+    // Clés HMAC: la première signe, toutes vérifient (rotation). Vide = cookie non signé
+    #[serde(default)]
+    pub secrets: Vec<String>,
+}
+
+impl Default for SessionCookie {
+    fn default() -> Self {
+        Self {
+            name: default_cookie_name(),
+            path: default_cookie_path(),
+            domain: String::new(),
+            secure: false,
+            http_only: default_http_only(),
+            same_site: None,
+            max_age: false,
+            lifetime: default_session_lifetime(),
+            secrets: vec![],
+        }
+    }
+}
+
+impl SessionCookie {
+    /// Valeur du cookie pour la session `id`: "<id>.<HMAC-SHA256 en hexadécimal>" si une
+    /// clé est configurée, l'identifiant seul sinon.
+    pub fn sign(&self, id: &str) -> String {
+        match self.secrets.first() {
+            Some(secret) => {
+                let signature: String = Self::mac(secret, id)
+                    .finalize()
+                    .into_bytes()
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                format!("{}.{}", id, signature)
+            }
+            None => id.to_string(),
+        }
+    }
+
+    /// Identifiant de session d'une valeur de cookie, `None` si elle est malformée ou que
+    /// sa signature ne correspond à aucune clé.
+    pub fn verify(&self, value: &str) -> Option<String> {
+        let id = match self.secrets.is_empty() {
+            true => value,
+            false => {
+                let (id, signature) = value.rsplit_once('.')?;
+                let signature = Self::decode_hex(signature)?;
+                // Comparaison en temps constant (verify_slice)
+                self.secrets
+                    .iter()
+                    .find(|secret| Self::mac(secret, id).verify_slice(&signature).is_ok())?;
+                id
+            }
+        };
+        Uuid::parse_str(id).ok().map(|_| id.to_string())
+    }
+
+    fn mac(secret: &str, id: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepte des clés de toute taille");
+        mac.update(id.as_bytes());
+        mac
+    }
+
+    fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+        if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+            return None;
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect()
+    }
+}
+// -------------------------------------------------------------------------------------
+
+// -------------------------------------------------------------------------------------
+// SESSION STORE
+// -------------------------------------------------------------------------------------
+/// Données d'une session, conservées d'une requête à l'autre par le `SessionStore`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionData {
+    pub values: HashMap<String, String>,
+    pub expires_at: i64, // timestamp (secondes) après lequel les données sont oubliées
+}
+
+impl Default for SessionData {
+    /// Données vides, gardées pour la durée de vie d'une session.
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+            expires_at: (Utc::now() + Duration::milliseconds(Session::SESSION_LIFETIME))
+                .timestamp(),
+        }
+    }
+}
+
+impl SessionData {
+    pub fn is_expired(&self) -> bool {
+        Utc::now().timestamp() > self.expires_at
+    }
+}
+
+/// Stockage des sessions par identifiant.
+pub trait SessionStore: fmt::Debug {
+    /// Données de la session `id`, `None` si elle est inconnue ou expirée.
+    fn load(&mut self, id: &str) -> Option<SessionData>;
+
+    fn save(&mut self, id: &str, data: &SessionData) -> io::Result<()>;
+
+    fn remove(&mut self, id: &str) -> io::Result<()>;
+
+    /// Supprime les sessions expirées. Renvoie le nombre de sessions supprimées.
+    fn sweep(&mut self) -> io::Result<usize>;
+
+    fn get(&mut self, id: &str, key: &str) -> Option<String> {
+        self.load(id).and_then(|data| data.values.get(key).cloned())
+    }
 
-        cookie_header
+    /// Modifie une valeur de la session (la crée au besoin); une valeur vide la supprime.
+    fn set(&mut self, id: &str, key: &str, value: &str) -> io::Result<()> {
+        let mut data = self.load(id).unwrap_or_default();
+        match value.is_empty() {
+            true => data.values.remove(key),
+            false => data.values.insert(key.to_string(), value.to_string()),
+        };
+        self.save(id, &data)
+    }
+}
+
+/// Stockage choisi par `[http.session_store]`.
+pub fn session_store(config: &SessionStoreConfig) -> Box<dyn SessionStore> {
+    match config.storage {
+        SessionStorage::Memory => Box::new(MemorySessionStore::default()),
+        SessionStorage::File => Box::new(FileSessionStore::new(&config.directory)),
+    }
+}
+
+impl Default for Box<dyn SessionStore> {
+    fn default() -> Self {
+        Box::new(MemorySessionStore::default())
+    }
+}
+
+/// Sessions gardées en mémoire: perdues à l'arrêt du serveur.
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    sessions: HashMap<String, SessionData>,
+}
+
+impl SessionStore for MemorySessionStore {
+    fn load(&mut self, id: &str) -> Option<SessionData> {
+        self.sessions
+            .get(id)
+            .filter(|data| !data.is_expired())
+            .cloned()
+    }
+
+    fn save(&mut self, id: &str, data: &SessionData) -> io::Result<()> {
+        self.sessions.insert(id.to_string(), data.clone());
+        Ok(())
+    }
+
+    fn remove(&mut self, id: &str) -> io::Result<()> {
+        self.sessions.remove(id);
+        Ok(())
+    }
+
+    fn sweep(&mut self) -> io::Result<usize> {
+        let count = self.sessions.len();
+        self.sessions.retain(|_, data| !data.is_expired());
+        Ok(count - self.sessions.len())
+    }
+}
+
+/// Sessions gardées dans `directory`, un fichier JSON `<id>.json` par session:
+/// elles survivent à un redémarrage.
+#[derive(Debug)]
+pub struct FileSessionStore {
+    directory: PathBuf,
+}
+
+impl FileSessionStore {
+    pub fn new(directory: &str) -> Self {
+        Self {
+            directory: PathBuf::from(directory),
+        }
+    }
+
+    /// Fichier de la session, `None` si l'identifiant pourrait sortir du dossier.
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        valid.then(|| self.directory.join(format!("{}.json", id)))
+    }
+
+    fn invalid_id() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, "invalid session id")
+    }
+
+    fn read(path: &Path) -> io::Result<SessionData> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn load(&mut self, id: &str) -> Option<SessionData> {
+        let path = self.path(id)?;
+        let data = Self::read(&path).ok()?;
+        if data.is_expired() {
+            let _ = fs::remove_file(path);
+            return None;
+        }
+        Some(data)
+    }
+
+    fn save(&mut self, id: &str, data: &SessionData) -> io::Result<()> {
+        let path = self.path(id).ok_or_else(Self::invalid_id)?;
+        fs::create_dir_all(&self.directory)?;
+        // Écrit à côté puis renommé: une lecture ne voit jamais une session à moitié écrite
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(data)?)?;
+        fs::rename(&temporary, path)
+    }
+
+    fn remove(&mut self, id: &str) -> io::Result<()> {
+        let path = self.path(id).ok_or_else(Self::invalid_id)?;
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn sweep(&mut self) -> io::Result<usize> {
+        let entries = match fs::read_dir(&self.directory) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            entries => entries?,
+        };
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            // Fichier illisible: session perdue, supprimée comme une session expirée
+            if Self::read(&path).map_or(true, |data| data.is_expired()) {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
     }
 }
 // -------------------------------------------------------------------------------------
@@ -114,6 +455,120 @@ mod tests {
     use std::thread;
     use std::time::Duration;
 
+    #[test]
+    fn test_memory_session_store() {
+        let mut store = MemorySessionStore::default();
+        assert_eq!(store.get("abc", "user"), None);
+        store.set("abc", "user", "alice").unwrap();
+        store.set("abc", "theme", "dark").unwrap();
+        assert_eq!(store.get("abc", "user"), Some("alice".to_string()));
+        store.set("abc", "theme", "").unwrap();
+        assert_eq!(store.load("abc").unwrap().values.len(), 1);
+
+        let mut data = store.load("abc").unwrap();
+        data.expires_at = Utc::now().timestamp() - 1;
+        store.save("abc", &data).unwrap();
+        assert!(
+            store.load("abc").is_none(),
+            "Les données expirées sont oubliées."
+        );
+
+        store.set("def", "user", "bob").unwrap();
+        assert_eq!(store.sweep().unwrap(), 1);
+        assert_eq!(store.sweep().unwrap(), 0);
+        assert!(store.load("def").is_some());
+    }
+
+    #[test]
+    fn test_file_session_store() {
+        let dir = std::env::temp_dir().join(format!("sessions_{}", std::process::id()));
+        let mut store = FileSessionStore::new(dir.to_str().unwrap());
+        store.set("4f9c-aa01", "user", "alice").unwrap();
+        assert!(store.set("../escape", "user", "x").is_err());
+        assert!(store.load("../escape").is_none());
+
+        // Relu par un nouveau store, comme après un redémarrage
+        let mut store = FileSessionStore::new(dir.to_str().unwrap());
+        assert_eq!(store.get("4f9c-aa01", "user"), Some("alice".to_string()));
+        store.remove("4f9c-aa01").unwrap();
+        assert!(store.load("4f9c-aa01").is_none());
+
+        // Purge: sessions expirées et fichiers illisibles
+        store.set("old", "user", "alice").unwrap();
+        store.set("current", "user", "bob").unwrap();
+        let mut data = store.load("old").unwrap();
+        data.expires_at = Utc::now().timestamp() - 1;
+        store.save("old", &data).unwrap();
+        fs::write(dir.join("broken.json"), "{").unwrap();
+        assert_eq!(store.sweep().unwrap(), 2);
+        assert_eq!(store.get("current", "user"), Some("bob".to_string()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_session_refresh() {
+        let mut session = Session::new();
+        session.validity_time = Utc::now() + chrono::Duration::milliseconds(1000);
+        assert!(session.remaining() <= 1000);
+        session.refresh();
+        assert!(session.remaining() > Session::SESSION_LIFETIME - 1000);
+
+        session.validity_time = Utc::now() - chrono::Duration::milliseconds(1);
+        assert!(session.is_expired());
+        assert_eq!(session.remaining(), 0);
+    }
+
+    #[test]
+    fn test_make_cookie() {
+        let cookie = Session::make_cookie(&SessionCookie::default(), "abc", 1000);
+        assert!(cookie.starts_with("Set-Cookie: cookie_01=abc; Path=/; Expires="));
+        assert!(cookie.ends_with("GMT; HttpOnly\r\n"));
+
+        let config = SessionCookie {
+            name: "sid".to_string(),
+            path: "/app".to_string(),
+            domain: "example.com".to_string(),
+            http_only: false,
+            same_site: Some(SameSite::None),
+            max_age: true,
+            ..SessionCookie::default()
+        };
+        let cookie = Session::make_cookie(&config, "abc", 90_000);
+        assert!(cookie.starts_with("Set-Cookie: sid=abc; Path=/app; Domain=example.com; Expires="));
+        assert!(cookie.ends_with("; Max-Age=90; Secure; SameSite=None\r\n"));
+    }
+
+    #[test]
+    fn test_signed_cookie() {
+        let id = "0f8fad5b-d9cb-469f-a165-70867728950e";
+        let unsigned = SessionCookie::default();
+        assert_eq!(unsigned.sign(id), id);
+        assert_eq!(unsigned.verify(id), Some(id.to_string()));
+        assert_eq!(unsigned.verify("../../etc/passwd"), None);
+
+        let old = SessionCookie {
+            secrets: vec!["old".to_string()],
+            ..SessionCookie::default()
+        };
+        let signed = old.sign(id);
+        assert_eq!(signed.len(), id.len() + 1 + 64);
+        assert_eq!(old.verify(&signed), Some(id.to_string()));
+        assert_eq!(old.verify(id), None, "Un cookie non signé est refusé.");
+
+        // Rotation: la nouvelle clé signe, l'ancienne est encore acceptée
+        let rotated = SessionCookie {
+            secrets: vec!["new".to_string(), "old".to_string()],
+            ..SessionCookie::default()
+        };
+        assert_eq!(rotated.verify(&signed), Some(id.to_string()));
+        assert_ne!(rotated.sign(id), signed);
+
+        let forged = signed.replace("0f8fad5b", "1f8fad5b");
+        assert_eq!(old.verify(&forged), None);
+        assert_eq!(old.verify(&(signed.clone() + "0")), None);
+        assert_eq!(old.verify(&format!("{}.zz", id)), None);
+    }
+
     #[test]
     fn test_session_not_expired() {
         let session = Session::new();
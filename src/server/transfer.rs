@@ -0,0 +1,234 @@
+use mio::net::TcpStream;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::Instant;
+
+use super::{
+    ArchiveTransfer, CgiResponse, CgiTransfer, FastCgiTransfer, ProxyTransfer, ScgiTransfer,
+};
+
+// -------------------------------------------------------------------------------------
+// TRANSFER
+// -------------------------------------------------------------------------------------
+/// Réponse dont l'envoi se poursuit sur les événements WRITABLE du Router.
+#[derive(Debug)]
+pub enum Transfer {
+    File(FileTransfer),
+    Archive(Box<ArchiveTransfer>),
+    Cgi(Box<CgiTransfer>),
+    FastCgi(Box<FastCgiTransfer>),
+    Scgi(Box<ScgiTransfer>),
+    Proxy(Box<ProxyTransfer>),
+}
+
+impl Transfer {
+    /// Renvoie `Ok(true)` lorsque la réponse a été entièrement envoyée.
+    pub fn advance(&mut self, stream: &mut TcpStream) -> io::Result<bool> {
+        match self {
+            Transfer::File(transfer) => transfer.advance(stream),
+            Transfer::Archive(transfer) => transfer.advance(stream),
+            Transfer::Cgi(transfer) => transfer.advance(stream),
+            Transfer::FastCgi(transfer) => transfer.advance(stream),
+            Transfer::Scgi(transfer) => transfer.advance(stream),
+            Transfer::Proxy(transfer) => transfer.advance(stream),
+        }
+    }
+
+    /// Fait avancer une réponse sans client (revalidation du cache): la sortie est ignorée.
+    pub fn advance_detached(&mut self) -> io::Result<bool> {
+        let mut sink = io::sink();
+        match self {
+            Transfer::Cgi(transfer) => transfer.advance(&mut sink),
+            Transfer::FastCgi(transfer) => transfer.advance(&mut sink),
+            Transfer::Scgi(transfer) => transfer.advance(&mut sink),
+            Transfer::Proxy(transfer) => transfer.advance(&mut sink),
+            _ => Ok(true),
+        }
+    }
+
+    /// Réponse relayée d'un script CGI, d'une application FastCGI, SCGI ou uwsgi,
+    /// ou d'un serveur HTTP amont.
+    pub fn cgi_response(&mut self) -> Option<&mut CgiResponse> {
+        match self {
+            Transfer::Cgi(transfer) => Some(&mut transfer.response),
+            Transfer::FastCgi(transfer) => Some(&mut transfer.response),
+            Transfer::Scgi(transfer) => Some(&mut transfer.response),
+            Transfer::Proxy(transfer) => Some(&mut transfer.response),
+            _ => None,
+        }
+    }
+
+    /// Échéance d'un script CGI ou d'un serveur amont, `None` sans limite.
+    pub fn deadline(&self) -> Option<Instant> {
+        match self {
+            Transfer::Cgi(transfer) => transfer.deadline(),
+            Transfer::Proxy(transfer) => transfer.deadline(),
+            _ => None,
+        }
+    }
+
+    /// Délai dépassé: le script est arrêté ou la connexion amont fermée, avec 504.
+    pub fn expire(&mut self) {
+        match self {
+            Transfer::Cgi(transfer) => transfer.expire(),
+            Transfer::Proxy(transfer) => transfer.expire(),
+            _ => {}
+        }
+    }
+
+    /// Tente un premier envoi et ne garde le transfert que s'il reste des données.
+    pub fn start(mut self, stream: &mut TcpStream) -> io::Result<Option<Self>> {
+        match self.advance(stream)? {
+            true => Ok(None),
+            false => Ok(Some(self)),
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
+
+// -------------------------------------------------------------------------------------
+// FILE TRANSFER
+// -------------------------------------------------------------------------------------
+// Taille maximale lue en mémoire à chaque tour pour le mode read/write
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Envoi d'un fichier statique depuis un descripteur ouvert, sans le charger en mémoire.
+/// Le transfert avance à chaque événement WRITABLE du Router jusqu'à épuisement.
+#[derive(Debug)]
+pub struct FileTransfer {
+    pub file: Option<File>,
+    pub offset: u64,
+    pub remaining: u64,
+    // En-têtes HTTP (puis morceaux lus en mode read/write) en attente d'envoi
+    pub pending: Vec<u8>,
+    pub use_sendfile: bool,
+}
+
+impl FileTransfer {
+    /// Prépare l'envoi de `length` octets de `file` précédés des en-têtes `head`.
+    pub fn new(file: File, length: u64, head: Vec<u8>) -> Self {
+        Self {
+            file: Some(file),
+            offset: 0,
+            remaining: length,
+            pending: head,
+            use_sendfile: cfg!(target_os = "linux"),
+        }
+    }
+
+    /// Prépare l'envoi d'une réponse déjà entièrement en mémoire (ex: fichier en cache).
+    pub fn from_bytes(response: Vec<u8>) -> Self {
+        Self {
+            file: None,
+            offset: 0,
+            remaining: 0,
+            pending: response,
+            use_sendfile: false,
+        }
+    }
+
+    /// Écrit autant que possible sans bloquer.
+    /// Renvoie `Ok(true)` lorsque tout le fichier a été envoyé.
+    pub fn advance(&mut self, stream: &mut TcpStream) -> io::Result<bool> {
+        loop {
+            // 1. Vider d'abord le tampon en attente (en-têtes ou morceau déjà lu)
+            while !self.pending.is_empty() {
+                match stream.write(&self.pending) {
+                    Ok(0) => return Err(io::Error::from(ErrorKind::WriteZero)),
+                    Ok(n) => {
+                        self.pending.drain(..n);
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if self.remaining == 0 {
+                let _ = stream.flush();
+                return Ok(true);
+            }
+
+            // 2. Envoyer le corps du fichier
+            if self.use_sendfile {
+                match self.send_file(stream) {
+                    Ok(_) => continue,
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) if e.kind() == ErrorKind::Unsupported => {
+                        // sendfile indisponible pour ce descripteur: repli sur read/write
+                        self.use_sendfile = false;
+                    }
+                    Err(e) => return Err(e),
+                }
+            } else {
+                self.read_chunk()?;
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn send_file(&mut self, stream: &mut TcpStream) -> io::Result<usize> {
+        use std::os::fd::AsRawFd;
+
+        let Some(file) = &self.file else {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        };
+        let count = self.remaining.min(isize::MAX as u64) as usize;
+        let mut offset = self.offset as libc::off_t;
+        // SAFETY: les deux descripteurs restent ouverts pendant l'appel et `offset` est valide.
+        let sent = unsafe {
+            libc::sendfile(
+                stream.as_raw_fd(),
+                file.as_raw_fd(),
+                &mut offset,
+                count,
+            )
+        };
+
+        if sent < 0 {
+            let err = io::Error::last_os_error();
+            return match err.raw_os_error() {
+                Some(libc::EINVAL) | Some(libc::ENOSYS) => {
+                    Err(io::Error::from(ErrorKind::Unsupported))
+                }
+                _ => Err(err),
+            };
+        }
+        if sent == 0 {
+            // Le fichier a été tronqué pendant l'envoi
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+
+        self.offset = offset as u64;
+        self.remaining -= sent as u64;
+        Ok(sent as usize)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn send_file(&mut self, _stream: &mut TcpStream) -> io::Result<usize> {
+        Err(io::Error::from(ErrorKind::Unsupported))
+    }
+
+    /// Lit le prochain morceau du fichier dans le tampon d'envoi (mode read/write).
+    fn read_chunk(&mut self) -> io::Result<()> {
+        use std::io::{Seek, SeekFrom};
+
+        let Some(file) = &mut self.file else {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        };
+        let size = self.remaining.min(CHUNK_SIZE as u64) as usize;
+        let mut buffer = vec![0; size];
+        file.seek(SeekFrom::Start(self.offset))?;
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        buffer.truncate(n);
+        self.offset += n as u64;
+        self.remaining -= n as u64;
+        self.pending = buffer;
+        Ok(())
+    }
+}
+// -------------------------------------------------------------------------------------
@@ -0,0 +1,370 @@
+use chrono::{DateTime, Duration, Utc};
+use mio::net::TcpStream;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use super::{
+    path_size, sanitize_filename, RawResponse, Request, Server, ServerError, ServerState, Transfer,
+    UploadUsage,
+};
+use crate::{base64_decode, Config};
+
+// -------------------------------------------------------------------------------------
+// TUS
+// -------------------------------------------------------------------------------------
+pub const TUS_ENDPOINT: &str = "/.tus";
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,expiration,termination";
+const TUS_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+pub fn default_upload_expiry() -> u64 {
+    24 * 60 * 60
+}
+
+/// Envoi en cours: le contenu est ajouté à `part` jusqu'à atteindre `length`,
+/// puis le fichier est déplacé sous le dossier `location` de root_directory.
+#[derive(Debug, Clone)]
+pub struct TusUpload {
+    pub id: String,
+    pub hostname: String,
+    pub length: u64,
+    pub offset: u64,
+    pub metadata: String,
+    pub part: PathBuf,
+    pub location: String,
+    pub filename: String,
+    pub expires: DateTime<Utc>,
+}
+
+impl TusUpload {
+    pub fn is_complete(&self) -> bool {
+        self.offset == self.length
+    }
+}
+
+/// Envois tus connus, indexés par identifiant.
+#[derive(Debug, Default)]
+pub struct TusStore {
+    pub uploads: HashMap<String, TusUpload>,
+}
+
+impl TusStore {
+    /// Supprime les envois expirés ainsi que leurs fichiers partiels.
+    pub fn purge(&mut self) {
+        let now = Utc::now();
+        self.uploads.retain(|_, upload| {
+            if upload.expires > now {
+                return true;
+            }
+            if !upload.is_complete() {
+                let _ = fs::remove_file(&upload.part);
+            }
+            false
+        });
+    }
+
+    /// Supprime les fichiers partiels orphelins (ex: après un redémarrage) plus vieux que `expiry`.
+    pub fn purge_staging(&self, staging: &Path, expiry: u64) {
+        let Ok(entries) = fs::read_dir(staging) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let known = self.uploads.values().any(|upload| upload.part == path);
+            let age = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .map_or(0, |age| age.as_secs());
+            if !known && age > expiry && path.extension().is_some_and(|ext| ext == "part") {
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+impl Server {
+    pub fn is_tus_path(path: &str) -> bool {
+        path == TUS_ENDPOINT || path.starts_with(&format!("{}/", TUS_ENDPOINT))
+    }
+
+    /// Point d'entrée tus 1.0: création (POST), reprise (HEAD), ajout (PATCH), abandon (DELETE).
+    pub fn handle_tus(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        cookie: &String,
+        config: &Config,
+        state: &mut ServerState,
+    ) -> Option<Transfer> {
+        let ServerState {
+            uploads: store,
+            upload_usage: usage,
+            ..
+        } = state;
+        let response = if self.upload_staging.is_empty() {
+            RawResponse::status(404)
+        } else {
+            store.purge();
+            store.purge_staging(Path::new(&self.upload_staging), self.upload_expiry);
+
+            let id = request
+                .path
+                .strip_prefix(TUS_ENDPOINT)
+                .unwrap_or_default()
+                .trim_matches('/');
+
+            let result = match (request.method.as_str(), id) {
+                ("OPTIONS", _) => Ok(RawResponse::status(204)
+                    .header("Tus-Version", TUS_VERSION)
+                    .header("Tus-Extension", TUS_EXTENSIONS)
+                    .header("Tus-Max-Size", &self.tus_max_size().to_string())),
+                _ if request.header("Tus-Resumable").map(|v| v.trim()) != Some(TUS_VERSION) => {
+                    Ok(RawResponse::status(412).header("Tus-Version", TUS_VERSION))
+                }
+                ("POST", "") => self.tus_create(request, store, usage),
+                ("HEAD", id) if !id.is_empty() => Ok(self.tus_head(id, store)),
+                ("PATCH", id) if !id.is_empty() => self.tus_patch(request, id, store, usage),
+                ("DELETE", id) if !id.is_empty() => self.tus_terminate(id, store),
+                _ => Ok(RawResponse::status(405)),
+            };
+
+            match result {
+                Ok(response) => response,
+                Err(e) => {
+                    Self::error_log(
+                        request,
+                        config,
+                        "handle_tus",
+                        file!(),
+                        line!(),
+                        ServerError::IOError(&e),
+                    );
+                    RawResponse::status(500)
+                }
+            }
+        };
+
+        let response = response.header("Tus-Resumable", TUS_VERSION);
+        self.send_raw_response(stream, request, config, cookie, response)
+    }
+
+    /// Taille maximale d'un envoi: upload_limit (kb) du serveur.
+    fn tus_max_size(&self) -> u64 {
+        self.upload_limit as u64 * 1024
+    }
+
+    fn tus_create(
+        &self,
+        request: &Request,
+        store: &mut TusStore,
+        usage: &mut UploadUsage,
+    ) -> io::Result<RawResponse> {
+        let Some(length) = request
+            .header("Upload-Length")
+            .and_then(|l| l.trim().parse::<u64>().ok())
+        else {
+            return Ok(RawResponse::status(400));
+        };
+        if length > self.tus_max_size() {
+            return Ok(RawResponse::status(413));
+        }
+        if let Err((status, _)) = self.check_upload_quota(&request.id_session, length, usage) {
+            return Ok(RawResponse::status(status));
+        }
+
+        // Upload-Metadata: "filename <base64>,location <base64>"
+        let raw_metadata = request
+            .header("Upload-Metadata")
+            .cloned()
+            .unwrap_or_default();
+        let Some(metadata) = parse_upload_metadata(&raw_metadata) else {
+            return Ok(RawResponse::status(400));
+        };
+        let filename = metadata.get("filename").cloned().unwrap_or_default();
+        let location = metadata.get("location").cloned().unwrap_or("/".to_string());
+        // Vérification anticipée du nom et de la règle d'envoi, refaite à la fin de l'envoi
+        if let Err((status, _)) = self.upload_target(&location, &filename) {
+            return Ok(RawResponse::status(status));
+        }
+
+        fs::create_dir_all(&self.upload_staging)?;
+        let id = Uuid::new_v4().simple().to_string();
+        let part = Path::new(&self.upload_staging).join(format!("{}.part", id));
+        fs::write(&part, b"")?;
+
+        let upload = TusUpload {
+            id: id.clone(),
+            hostname: self.hostname.clone(),
+            length,
+            offset: 0,
+            metadata: raw_metadata.trim().to_string(),
+            part,
+            location,
+            filename: sanitize_filename(&filename),
+            expires: Utc::now() + Duration::seconds(self.upload_expiry as i64),
+        };
+        if upload.is_complete() {
+            if let Some(status) = self.tus_finish(&upload, usage)? {
+                return Ok(RawResponse::status(status));
+            }
+        }
+
+        let response = RawResponse::status(201)
+            .header("Location", &format!("{}/{}", TUS_ENDPOINT, id))
+            .header("Upload-Expires", &http_date(&upload.expires));
+        store.uploads.insert(id, upload);
+        Ok(response)
+    }
+
+    fn tus_head(&self, id: &str, store: &TusStore) -> RawResponse {
+        match store.uploads.get(id) {
+            Some(upload) if upload.hostname == self.hostname => {
+                let mut response = RawResponse::status(200)
+                    .header("Upload-Offset", &upload.offset.to_string())
+                    .header("Upload-Length", &upload.length.to_string())
+                    .header("Upload-Expires", &http_date(&upload.expires))
+                    .header("Cache-Control", "no-store");
+                if !upload.metadata.is_empty() {
+                    response = response.header("Upload-Metadata", &upload.metadata);
+                }
+                response
+            }
+            _ => RawResponse::status(404),
+        }
+    }
+
+    fn tus_patch(
+        &self,
+        request: &Request,
+        id: &str,
+        store: &mut TusStore,
+        usage: &mut UploadUsage,
+    ) -> io::Result<RawResponse> {
+        let Some(upload) = store
+            .uploads
+            .get_mut(id)
+            .filter(|upload| upload.hostname == self.hostname)
+        else {
+            return Ok(RawResponse::status(404));
+        };
+        if request.header("Content-Type").map(|t| t.trim()) != Some(TUS_CONTENT_TYPE) {
+            return Ok(RawResponse::status(415));
+        }
+        let offset = request
+            .header("Upload-Offset")
+            .and_then(|o| o.trim().parse::<u64>().ok());
+        if offset != Some(upload.offset) {
+            return Ok(RawResponse::status(409));
+        }
+
+        let length = request.content_length.unwrap_or(request.body_byte.len());
+        let chunk = &request.body_byte[..length.min(request.body_byte.len())];
+        if upload.offset + chunk.len() as u64 > upload.length {
+            return Ok(RawResponse::status(400));
+        }
+
+        if let Err((status, _)) =
+            self.check_upload_quota(&request.id_session, chunk.len() as u64, usage)
+        {
+            return Ok(RawResponse::status(status));
+        }
+
+        // Un ajout interrompu a pu laisser des octets au-delà de l'offset connu: on les écarte
+        let mut file = OpenOptions::new().write(true).open(&upload.part)?;
+        file.set_len(upload.offset)?;
+        file.seek(SeekFrom::Start(upload.offset))?;
+        file.write_all(chunk)?;
+        upload.offset += chunk.len() as u64;
+        usage.record(
+            &self.hostname,
+            &request.id_session,
+            chunk.len() as u64,
+            self.session_quota_window,
+        );
+        upload.expires = Utc::now() + Duration::seconds(self.upload_expiry as i64);
+
+        if upload.is_complete() {
+            if let Some(status) = self.tus_finish(upload, usage)? {
+                return Ok(RawResponse::status(status));
+            }
+        }
+
+        Ok(RawResponse::status(204)
+            .header("Upload-Offset", &upload.offset.to_string())
+            .header("Upload-Expires", &http_date(&upload.expires)))
+    }
+
+    fn tus_terminate(&self, id: &str, store: &mut TusStore) -> io::Result<RawResponse> {
+        match store.uploads.get(id) {
+            Some(upload) if upload.hostname == self.hostname => {
+                if !upload.is_complete() {
+                    fs::remove_file(&upload.part)?;
+                }
+                store.uploads.remove(id);
+                Ok(RawResponse::status(204))
+            }
+            _ => Ok(RawResponse::status(404)),
+        }
+    }
+
+    /// Déplace le fichier complet vers son emplacement final selon la règle d'envoi du dossier.
+    /// Renvoie le code HTTP d'erreur si la règle refuse désormais le fichier (ex: collision).
+    fn tus_finish(&self, upload: &TusUpload, usage: &mut UploadUsage) -> io::Result<Option<u16>> {
+        let target = match self.upload_target(&upload.location, &upload.filename) {
+            Ok(target) => target,
+            Err((status, _)) => {
+                fs::remove_file(&upload.part)?;
+                return Ok(Some(status));
+            }
+        };
+
+        // rename échoue entre deux systèmes de fichiers: copie puis suppression
+        let replaced = path_size(&target);
+        if fs::rename(&upload.part, &target).is_err() {
+            fs::copy(&upload.part, &target)?;
+            fs::remove_file(&upload.part)?;
+        }
+        usage.update_disk(&self.root_directory, upload.length, replaced);
+        Ok(None)
+    }
+}
+// -------------------------------------------------------------------------------------
+
+/// Décode l'en-tête Upload-Metadata: paires "clé valeur_base64" séparées par des virgules.
+pub fn parse_upload_metadata(header: &str) -> Option<HashMap<String, String>> {
+    let mut metadata = HashMap::new();
+    for pair in header.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once(' ').unwrap_or((pair, ""));
+        let value = base64_decode(value.trim())?;
+        metadata.insert(key.to_string(), String::from_utf8(value).ok()?);
+    }
+    Some(metadata)
+}
+
+fn http_date(date: &DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_upload_metadata() {
+        let metadata = parse_upload_metadata(
+            "filename d29ybGRfZG9taW5hdGlvbl9wbGFuLnBkZg==,location L2Q=,is_confidential",
+        )
+        .unwrap();
+        assert_eq!(metadata["filename"], "world_domination_plan.pdf");
+        assert_eq!(metadata["location"], "/d");
+        assert_eq!(metadata["is_confidential"], "");
+
+        assert!(parse_upload_metadata("filename *invalid*").is_none());
+    }
+}
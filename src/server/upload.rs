@@ -0,0 +1,480 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{DirectoryElement, Server};
+use crate::{CollisionPolicy, UploadPolicy};
+
+// -------------------------------------------------------------------------------------
+// UPLOAD
+// -------------------------------------------------------------------------------------
+// Longueur maximale d'un nom de fichier (octets) sur la plupart des systèmes de fichiers
+const FILENAME_MAX: usize = 255;
+
+pub fn default_quota_window() -> u64 {
+    60 * 60
+}
+
+/// Octets envoyés par une session pendant la fenêtre de quota en cours.
+#[derive(Debug, Clone)]
+pub struct SessionUsage {
+    pub bytes: u64,
+    pub window_end: DateTime<Utc>,
+}
+
+/// Consommation des quotas d'envoi de chaque serveur: octets envoyés par session, et taille
+/// des fichiers sous root_directory tenue à jour à chaque écriture ou suppression.
+#[derive(Debug, Default)]
+pub struct UploadUsage {
+    pub sessions: HashMap<(String, String), SessionUsage>, // (serveur, identifiant de session)
+    pub disk: HashMap<String, u64>,                        // octets par root_directory
+}
+
+impl UploadUsage {
+    /// Octets envoyés par la session au serveur dans sa fenêtre courante.
+    pub fn session_bytes(&self, server: &str, session_id: &str) -> u64 {
+        self.sessions
+            .get(&(server.to_string(), session_id.to_string()))
+            .filter(|usage| Utc::now() < usage.window_end)
+            .map_or(0, |usage| usage.bytes)
+    }
+
+    /// Ajoute `bytes` à la session; une nouvelle fenêtre de `window` secondes
+    /// commence si la précédente est terminée.
+    pub fn record(&mut self, server: &str, session_id: &str, bytes: u64, window: u64) {
+        let now = Utc::now();
+        self.sessions.retain(|_, usage| now < usage.window_end);
+        let usage = self
+            .sessions
+            .entry((server.to_string(), session_id.to_string()))
+            .or_insert(SessionUsage {
+                bytes: 0,
+                window_end: now + Duration::seconds(window as i64),
+            });
+        usage.bytes += bytes;
+    }
+
+    /// Taille des fichiers sous `root`, parcourue une seule fois au premier appel.
+    pub fn disk_bytes(&mut self, root: &str) -> u64 {
+        *self
+            .disk
+            .entry(root.to_string())
+            .or_insert_with(|| directory_size(Path::new(root)))
+    }
+
+    /// Ajuste la taille de `root` après l'écriture de `added` octets et la suppression de
+    /// `removed` octets; sans effet tant qu'elle n'a pas été calculée.
+    pub fn update_disk(&mut self, root: &str, added: u64, removed: u64) {
+        if let Some(bytes) = self.disk.get_mut(root) {
+            *bytes = (*bytes + added).saturating_sub(removed);
+        }
+    }
+}
+
+/// Consommation affichée dans le listing (tailles lisibles, `None` sans quota).
+#[derive(Debug, Serialize)]
+pub struct QuotaStatus {
+    pub disk_usage: Option<String>,
+    pub disk_quota: Option<String>,
+    pub session_usage: Option<String>,
+    pub session_quota: Option<String>,
+}
+
+impl Server {
+    /// Règle d'envoi applicable au dossier `url_dir`: celle dont la location est
+    /// le plus long préfixe, sinon la règle par défaut (écrasement, toutes extensions).
+    pub fn upload_policy(&self, url_dir: &str) -> UploadPolicy {
+        let url_dir = url_dir.trim_end_matches('/');
+        self.upload_policies
+            .iter()
+            .filter(|policy| {
+                let location = policy.location.trim_end_matches('/');
+                url_dir == location || url_dir.starts_with(&format!("{}/", location))
+            })
+            .max_by_key(|policy| policy.location.trim_end_matches('/').len())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Valide le nom d'un fichier envoyé dans `url_dir` et renvoie le chemin où l'écrire,
+    /// selon la politique de collision du dossier.
+    /// Renvoie le code HTTP et le message d'erreur à envoyer en cas d'échec.
+    pub fn upload_target(
+        &self,
+        url_dir: &str,
+        filename: &str,
+    ) -> Result<PathBuf, (u16, &'static str)> {
+        let filename = sanitize_filename(filename);
+        if filename.is_empty() {
+            return Err((400, "Bad Request: Invalid file name"));
+        }
+
+        let policy = self.upload_policy(url_dir);
+        self.check_upload_path(&format!("{}/{}", url_dir.trim_end_matches('/'), filename))?;
+
+        let Some(directory) = self.resolve_path("/", url_dir) else {
+            return Err((403, "Forbidden"));
+        };
+        if !directory.is_dir() {
+            return Err((409, "Conflict: Destination folder does not exist"));
+        }
+
+        let target = directory.join(&filename);
+        if !target.exists() {
+            return Ok(target);
+        }
+        match policy.collision {
+            CollisionPolicy::Overwrite if target.is_dir() => {
+                Err((409, "Conflict: A folder with this name already exists"))
+            }
+            CollisionPolicy::Overwrite => Ok(target),
+            CollisionPolicy::Reject => Err((409, "Conflict: File already exists")),
+            CollisionPolicy::Rename => Ok(free_name(&directory, &filename)),
+        }
+    }
+
+    /// Vérifie qu'un fichier peut être envoyé à `url_path`: extension acceptée par la règle
+    /// du dossier, et fichier qui ne serait pas exécuté comme script CGI ou par une passerelle.
+    pub fn check_upload_path(&self, url_path: &str) -> Result<(), (u16, &'static str)> {
+        let (url_dir, filename) = url_path.rsplit_once('/').unwrap_or(("", url_path));
+        if !extension_allowed(&self.upload_policy(url_dir), filename) {
+            return Err((415, "Unsupported Media Type: File extension not allowed"));
+        }
+        if self.is_script_path(url_path) {
+            return Err((403, "Forbidden: Scripts cannot be uploaded"));
+        }
+        Ok(())
+    }
+
+    /// `check_upload_path` pour chaque fichier de `source` (fichier ou dossier) copié ou
+    /// déplacé vers `url_path`.
+    pub fn check_upload_tree(
+        &self,
+        source: &Path,
+        url_path: &str,
+    ) -> Result<(), (u16, &'static str)> {
+        let Ok(file_type) = fs::symlink_metadata(source).map(|m| m.file_type()) else {
+            return Ok(());
+        };
+        if !file_type.is_dir() {
+            return self.check_upload_path(url_path);
+        }
+        for entry in fs::read_dir(source).into_iter().flatten().flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let url_path = format!("{}/{}", url_path.trim_end_matches('/'), name);
+            self.check_upload_tree(&entry.path(), &url_path)?;
+        }
+        Ok(())
+    }
+
+    /// Applique les règles des envois à la destination d'un renommage ou d'un déplacement
+    /// (deux chemins validés par `resolve_path`): nom, extensions, scripts et politique de
+    /// collision du dossier. Renvoie le chemin où déplacer `source` et s'il faut écraser
+    /// la destination existante.
+    pub fn move_target(
+        &self,
+        source: &Path,
+        target: &Path,
+        overwrite: bool,
+    ) -> Result<(PathBuf, bool), (u16, &'static str)> {
+        let root = Path::new(self.root_directory.trim_end_matches('/'));
+        let relative = target.strip_prefix(root).unwrap_or(target);
+        let dest_url = format!("/{}", relative.to_string_lossy());
+        let (dest_dir, dest_name) = dest_url.rsplit_once('/').unwrap_or_default();
+        if sanitize_filename(dest_name) != dest_name {
+            return Err((400, "Bad Request: Invalid file name"));
+        }
+        self.check_upload_tree(source, &dest_url)?;
+
+        if !target.exists() || source == target {
+            return Ok((target.to_path_buf(), false));
+        }
+        match self.upload_policy(dest_dir).collision {
+            CollisionPolicy::Overwrite if overwrite => Ok((target.to_path_buf(), true)),
+            CollisionPolicy::Rename => {
+                let directory = target.parent().unwrap_or(root);
+                Ok((free_name(directory, dest_name), false))
+            }
+            _ => Err((409, "Conflict: destination already exists")),
+        }
+    }
+
+    /// Vérifie qu'un envoi de `size` octets respecte le quota du serveur (507)
+    /// et celui de la session sur la fenêtre courante (429).
+    pub fn check_upload_quota(
+        &self,
+        session_id: &str,
+        size: u64,
+        usage: &mut UploadUsage,
+    ) -> Result<(), (u16, &'static str)> {
+        if self.upload_quota > 0 && self.disk_usage(usage) + size > self.upload_quota * 1024 {
+            return Err((507, "Insufficient Storage: Server upload quota exceeded"));
+        }
+        if self.session_upload_quota > 0
+            && usage.session_bytes(&self.hostname, session_id) + size
+                > self.session_upload_quota * 1024
+        {
+            return Err((429, "Too Many Requests: Session upload quota exceeded"));
+        }
+        Ok(())
+    }
+
+    /// Taille totale des fichiers sous root_directory.
+    pub fn disk_usage(&self, usage: &mut UploadUsage) -> u64 {
+        usage.disk_bytes(&self.root_directory)
+    }
+
+    /// Comptabilise un envoi de `size` octets de la session, écrit sous root_directory
+    /// à la place de `replaced` octets (fichier écrasé).
+    pub fn record_upload(
+        &self,
+        session_id: &str,
+        size: u64,
+        replaced: u64,
+        usage: &mut UploadUsage,
+    ) {
+        usage.record(&self.hostname, session_id, size, self.session_quota_window);
+        usage.update_disk(&self.root_directory, size, replaced);
+    }
+
+    pub fn quota_status(&self, session_id: &str, usage: &mut UploadUsage) -> QuotaStatus {
+        let human = |kb: u64| DirectoryElement::human_size(kb * 1024);
+        let enabled = |kb: u64| Some(kb).filter(|kb| *kb > 0);
+        QuotaStatus {
+            disk_usage: enabled(self.upload_quota)
+                .map(|_| DirectoryElement::human_size(self.disk_usage(usage))),
+            disk_quota: enabled(self.upload_quota).map(human),
+            session_usage: enabled(self.session_upload_quota).map(|_| {
+                DirectoryElement::human_size(usage.session_bytes(&self.hostname, session_id))
+            }),
+            session_quota: enabled(self.session_upload_quota).map(human),
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
+
+/// Nettoie un nom de fichier envoyé par le client: retire les caractères de contrôle,
+/// remplace les séparateurs de chemin et refuse "." et "..".
+pub fn sanitize_filename(filename: &str) -> String {
+    // Certains navigateurs envoient le chemin complet ("C:\Users\...\photo.png")
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let mut name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string();
+
+    while name.len() > FILENAME_MAX {
+        name.pop();
+    }
+    match name.as_str() {
+        "." | ".." => String::new(),
+        _ => name,
+    }
+}
+
+fn extension_allowed(policy: &UploadPolicy, filename: &str) -> bool {
+    let extension = Path::new(filename)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let matches = |list: &Vec<String>| {
+        list.iter()
+            .any(|ext| ext.trim_start_matches('.').to_lowercase() == extension)
+    };
+
+    (policy.allowed_extensions.is_empty() || matches(&policy.allowed_extensions))
+        && !matches(&policy.denied_extensions)
+}
+
+/// Taille d'un fichier, ou des fichiers d'un dossier.
+pub fn path_size(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => directory_size(path),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Taille cumulée des fichiers d'un dossier (sans suivre les liens symboliques).
+fn directory_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            Some((
+                entry.path(),
+                entry.file_type().ok()?,
+                entry.metadata().ok()?,
+            ))
+        })
+        .map(|(path, file_type, metadata)| match file_type.is_dir() {
+            true => directory_size(&path),
+            false => metadata.len(),
+        })
+        .sum()
+}
+
+/// Premier nom libre de la forme "file (1).png", "file (2).png"...
+fn free_name(directory: &Path, filename: &str) -> PathBuf {
+    let path = Path::new(filename);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|i| directory.join(format!("{} ({}){}", stem, i, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| directory.join(filename))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("C:\\Users\\me\\photo.png"), "photo.png");
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_filename("a\u{0}b\r\nc?.txt"), "abc_.txt");
+        assert_eq!(sanitize_filename(".."), "");
+        assert_eq!(sanitize_filename("  "), "");
+    }
+
+    #[test]
+    fn test_extension_allowed() {
+        let policy = UploadPolicy {
+            location: "/".to_string(),
+            allowed_extensions: vec!["png".to_string(), ".JPG".to_string()],
+            denied_extensions: vec!["jpg".to_string()],
+            ..Default::default()
+        };
+        assert!(extension_allowed(&policy, "a.PNG"));
+        assert!(!extension_allowed(&policy, "a.jpg"));
+        assert!(!extension_allowed(&policy, "a.exe"));
+        assert!(!extension_allowed(&policy, "Makefile"));
+    }
+
+    #[test]
+    fn test_session_usage_window() {
+        let mut usage = UploadUsage::default();
+        usage.record("srv", "a", 100, 3600);
+        usage.record("srv", "a", 50, 3600);
+        assert_eq!(usage.session_bytes("srv", "a"), 150);
+        assert_eq!(usage.session_bytes("srv", "b"), 0);
+        // Chaque serveur a son propre quota
+        assert_eq!(usage.session_bytes("other", "a"), 0);
+
+        // Fenêtre terminée: le compteur repart de zéro
+        let key = ("srv".to_string(), "a".to_string());
+        usage.sessions.get_mut(&key).unwrap().window_end = Utc::now() - Duration::seconds(1);
+        assert_eq!(usage.session_bytes("srv", "a"), 0);
+        usage.record("srv", "a", 10, 3600);
+        assert_eq!(usage.session_bytes("srv", "a"), 10);
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        let dir = std::env::temp_dir().join(format!("upload_usage_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), b"12345").unwrap();
+        fs::write(dir.join("sub/b.txt"), b"123").unwrap();
+        let root = dir.to_str().unwrap();
+
+        let mut usage = UploadUsage::default();
+        usage.update_disk(root, 100, 0); // pas encore calculée: ignoré
+        assert_eq!(usage.disk_bytes(root), 8);
+
+        // Tenue à jour sans nouveau parcours
+        fs::write(dir.join("c.txt"), b"1234567890").unwrap();
+        usage.update_disk(root, 10, 0);
+        usage.update_disk(root, 0, 5);
+        assert_eq!(usage.disk_bytes(root), 13);
+        usage.update_disk(root, 0, 100);
+        assert_eq!(usage.disk_bytes(root), 0);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_move_target() {
+        let dir = std::env::temp_dir().join(format!("upload_move_{}", std::process::id()));
+        fs::create_dir_all(dir.join("cgi-bin")).unwrap();
+        fs::write(dir.join("x.txt"), b"puts 1").unwrap();
+        fs::write(dir.join("y.txt"), b"").unwrap();
+        let mut server = Server::new(
+            "127.0.0.1".to_string(),
+            "localhost".to_string(),
+            vec![8080],
+            dir.to_string_lossy().to_string(),
+            String::new(),
+            String::new(),
+            0,
+            vec![],
+            false,
+            vec![],
+            vec![],
+        );
+        server.cgi_bin = "/cgi-bin".to_string();
+        let source = dir.join("x.txt");
+
+        // Renommage en script ou déplacement dans cgi-bin refusés
+        assert_eq!(
+            server
+                .move_target(&source, &dir.join("x.rb"), false)
+                .unwrap_err()
+                .0,
+            403
+        );
+        let into_cgi_bin = dir.join("cgi-bin/x.txt");
+        assert_eq!(
+            server
+                .move_target(&source, &into_cgi_bin, false)
+                .unwrap_err()
+                .0,
+            403
+        );
+        let bad_name = dir.join("x?.txt");
+        assert_eq!(
+            server.move_target(&source, &bad_name, false).unwrap_err().0,
+            400
+        );
+
+        // Collision: écrasée sur demande avec la politique "overwrite", renommée avec "rename"
+        let target = dir.join("z.txt");
+        assert_eq!(
+            server.move_target(&source, &target, false),
+            Ok((target, false))
+        );
+        let existing = dir.join("y.txt");
+        assert_eq!(
+            server.move_target(&source, &existing, false).unwrap_err().0,
+            409
+        );
+        assert_eq!(
+            server.move_target(&source, &existing, true),
+            Ok((existing.clone(), true))
+        );
+        server.upload_policies = vec![UploadPolicy {
+            location: "/".to_string(),
+            collision: CollisionPolicy::Rename,
+            ..Default::default()
+        }];
+        let (target, overwrite) = server.move_target(&source, &existing, true).unwrap();
+        assert_eq!((target, overwrite), (dir.join("y (1).txt"), false));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
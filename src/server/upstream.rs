@@ -0,0 +1,744 @@
+use mio::net::{TcpStream, UnixStream};
+use mio::{Interest, Registry, Token};
+use serde::Deserialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::{Request, Server, ServerError};
+use crate::Config;
+
+// -------------------------------------------------------------------------------------
+// UPSTREAM
+// -------------------------------------------------------------------------------------
+/// Adresse d'un service résolue au chargement de la configuration: une résolution DNS
+/// dans la boucle d'événements bloquerait tous les clients.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpstreamTarget {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+    Unresolved(String), // erreur de résolution, renvoyée à chaque connexion
+}
+
+impl Default for UpstreamTarget {
+    fn default() -> Self {
+        Self::Unresolved("address not resolved".to_string())
+    }
+}
+
+impl UpstreamTarget {
+    /// Résout "127.0.0.1:9000", "backend:9000" ou "unix:/chemin/vers/socket" (bloquant).
+    pub fn resolve(address: &str) -> Self {
+        if let Some(path) = address.strip_prefix("unix:") {
+            return Self::Unix(PathBuf::from(path));
+        }
+        match address.to_socket_addrs().map(|mut addrs| addrs.next()) {
+            Ok(Some(addr)) => Self::Tcp(addr),
+            Ok(None) => Self::Unresolved(format!("{}: Adresse du service invalide", address)),
+            Err(e) => Self::Unresolved(format!("{}: {}", address, e)),
+        }
+    }
+}
+
+/// Connexion non bloquante vers un service local (FastCGI, SCGI, uwsgi...).
+#[derive(Debug)]
+pub enum UpstreamSocket {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl UpstreamSocket {
+    pub fn connect(target: &UpstreamTarget) -> io::Result<Self> {
+        match target {
+            UpstreamTarget::Tcp(addr) => Ok(Self::Tcp(TcpStream::connect(*addr)?)),
+            UpstreamTarget::Unix(path) => Ok(Self::Unix(UnixStream::connect(path)?)),
+            UpstreamTarget::Unresolved(error) => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput, error.clone()))
+            }
+        }
+    }
+
+    pub fn register(&mut self, registry: &Registry, token: Token) -> io::Result<()> {
+        let interest = Interest::READABLE | Interest::WRITABLE;
+        match self {
+            Self::Tcp(socket) => registry.register(socket, token, interest),
+            Self::Unix(socket) => registry.register(socket, token, interest),
+        }
+    }
+
+    pub fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        match self {
+            Self::Tcp(socket) => registry.deregister(socket),
+            Self::Unix(socket) => registry.deregister(socket),
+        }
+    }
+}
+
+/// Adresse de connexion d'un serveur HTTP amont: "http://backend:8080/" -> "backend:8080"
+/// (port 80 par défaut); "unix:/chemin" est gardé tel quel.
+pub fn upstream_address(upstream: &str) -> String {
+    if upstream.starts_with("unix:") {
+        return upstream.to_string();
+    }
+    let address = upstream
+        .strip_prefix("http://")
+        .unwrap_or(upstream)
+        .trim_end_matches('/');
+    match address.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => address.to_string(),
+        _ => format!("{}:80", address),
+    }
+}
+
+/// En-tête Host d'une requête vers un serveur HTTP amont.
+pub fn upstream_host(upstream: &str) -> String {
+    match upstream.starts_with("unix:") {
+        true => "localhost".to_string(),
+        false => upstream_address(upstream),
+    }
+}
+
+impl Read for UpstreamSocket {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(socket) => socket.read(buffer),
+            Self::Unix(socket) => socket.read(buffer),
+        }
+    }
+}
+
+impl Write for UpstreamSocket {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(socket) => socket.write(buffer),
+            Self::Unix(socket) => socket.write(buffer),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(socket) => socket.flush(),
+            Self::Unix(socket) => socket.flush(),
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
+
+// -------------------------------------------------------------------------------------
+// UPSTREAM POOLS
+// -------------------------------------------------------------------------------------
+// Tentatives de hachage avant de revenir au round-robin (ip_hash)
+const IP_HASH_TRIES: u64 = 20;
+// Taille maximale lue pour la ligne de statut d'un test de santé
+const PROBE_READ_SIZE: usize = 1024;
+
+pub fn default_weight() -> u32 {
+    1
+}
+
+pub fn default_max_fails() -> u32 {
+    1
+}
+
+pub fn default_fail_timeout() -> u64 {
+    10
+}
+
+pub fn default_check_interval() -> u64 {
+    5
+}
+
+pub fn default_check_timeout() -> u64 {
+    2
+}
+
+/// Choix du serveur d'un groupe pour chaque requête.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceStrategy {
+    #[default]
+    RoundRobin, // à tour de rôle, proportionnellement aux poids
+    LeastConn, // le moins de requêtes en cours (rapportées au poids)
+    IpHash,    // toujours le même serveur pour une adresse client
+}
+
+/// Groupe nommé de serveurs amont ([http.upstreams.<nom>] dans config.toml).
+#[derive(Debug, Deserialize, Clone)]
+pub struct UpstreamGroup {
+    #[serde(default)]
+    pub strategy: BalanceStrategy,
+    pub servers: Vec<UpstreamPeer>,
+    #[serde(default)]
+    pub health_check: Option<HealthCheck>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct UpstreamPeer {
+    pub address: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default = "default_max_fails")]
+    pub max_fails: u32, // échecs pendant fail_timeout avant mise à l'écart, 0 = jamais
+    #[serde(default = "default_fail_timeout")]
+    pub fail_timeout: u64, // secondes: fenêtre de comptage et durée de mise à l'écart
+}
+
+/// Requête GET envoyée régulièrement à chaque serveur: 2xx ou 3xx = en bonne santé.
+#[derive(Debug, Deserialize, Clone)]
+pub struct HealthCheck {
+    pub path: String,
+    #[serde(default = "default_check_interval")]
+    pub interval: u64, // secondes
+    #[serde(default = "default_check_timeout")]
+    pub timeout: u64, // secondes
+}
+
+/// Serveur d'un groupe, tel que désigné dans les comptes rendus des requêtes proxy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerId {
+    pub group: String,
+    pub index: usize,
+}
+
+/// Compte rendu d'une requête proxy, appliqué au groupe par le Router.
+#[derive(Debug)]
+pub enum PeerReport {
+    Connected(PeerId),
+    Released { peer: PeerId, failed: bool },
+}
+
+#[derive(Debug)]
+struct PeerState {
+    config: UpstreamPeer,
+    target: UpstreamTarget,
+    active: usize,
+    fails: u32,
+    fails_since: Option<Instant>,
+    down_until: Option<Instant>,
+    healthy: bool,
+    // Poids courant du round-robin pondéré "lisse"
+    current: i64,
+}
+
+impl PeerState {
+    fn is_available(&self, now: Instant) -> bool {
+        self.healthy && self.down_until.is_none_or(|until| until <= now)
+    }
+}
+
+#[derive(Debug)]
+struct UpstreamPool {
+    strategy: BalanceStrategy,
+    peers: Vec<PeerState>,
+    health_check: Option<HealthCheck>,
+    next_check: Instant,
+}
+
+impl UpstreamPool {
+    /// Serveur suivant parmi `available` selon les poids (répartition régulière).
+    fn round_robin(&mut self, available: &[usize]) -> usize {
+        let total: i64 = available
+            .iter()
+            .map(|&i| self.peers[i].config.weight as i64)
+            .sum();
+        let mut best = available[0];
+        for &i in available {
+            self.peers[i].current += self.peers[i].config.weight as i64;
+            if self.peers[i].current > self.peers[best].current {
+                best = i;
+            }
+        }
+        self.peers[best].current -= total;
+        best
+    }
+
+    fn least_conn(&mut self, available: &[usize]) -> usize {
+        // active / weight le plus faible, les ex aequo à tour de rôle
+        let load = |i: usize| {
+            (
+                self.peers[i].active as u64,
+                self.peers[i].config.weight as u64,
+            )
+        };
+        let (active, weight) = available
+            .iter()
+            .map(|&i| load(i))
+            .min_by(|(a, wa), (b, wb)| (a * wb).cmp(&(b * wa)))
+            .unwrap_or((0, 1));
+        let tied: Vec<usize> = available
+            .iter()
+            .copied()
+            .filter(|&i| {
+                let (a, w) = load(i);
+                a * weight == active * w
+            })
+            .collect();
+        self.round_robin(&tied)
+    }
+
+    fn ip_hash(&mut self, available: &[usize], client_ip: &str) -> usize {
+        // Calculé sur tous les serveurs: un serveur écarté ne déplace pas les autres clients
+        let total: u64 = self
+            .peers
+            .iter()
+            .map(|peer| peer.config.weight as u64)
+            .sum();
+        for attempt in 0..IP_HASH_TRIES {
+            let mut hasher = DefaultHasher::new();
+            (client_ip, attempt).hash(&mut hasher);
+            let mut point = hasher.finish() % total.max(1);
+            let index = self
+                .peers
+                .iter()
+                .position(|peer| match point.checked_sub(peer.config.weight as u64) {
+                    Some(rest) => {
+                        point = rest;
+                        false
+                    }
+                    None => true,
+                })
+                .unwrap_or(0);
+            if available.contains(&index) {
+                return index;
+            }
+        }
+        self.round_robin(available)
+    }
+}
+
+/// Sonde de santé en cours vers un serveur.
+#[derive(Debug)]
+struct HealthProbe {
+    group: String,
+    index: usize,
+    socket: UpstreamSocket,
+    outgoing: Vec<u8>,
+    written: usize,
+    incoming: Vec<u8>,
+    deadline: Instant,
+}
+
+impl HealthProbe {
+    /// Renvoie le résultat une fois la ligne de statut reçue (ou la connexion en échec).
+    fn advance(&mut self) -> Option<Result<(), String>> {
+        while self.written < self.outgoing.len() {
+            match self.socket.write(&self.outgoing[self.written..]) {
+                Ok(n) => self.written += n,
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::NotConnected =>
+                {
+                    return None
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e.to_string())),
+            }
+        }
+        let mut buffer = [0; PROBE_READ_SIZE];
+        loop {
+            if let Some(end) = self.incoming.iter().position(|b| *b == b'\n') {
+                let line = String::from_utf8_lossy(&self.incoming[..end]).to_string();
+                let status = line
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|code| code.parse::<u16>().ok());
+                return Some(match status {
+                    Some(200..=399) => Ok(()),
+                    _ => Err(format!("health check answered {:?}", line.trim_end())),
+                });
+            }
+            if self.incoming.len() > PROBE_READ_SIZE {
+                return Some(Err("invalid health check response".to_string()));
+            }
+            match self.socket.read(&mut buffer) {
+                Ok(0) => return Some(Err("connection closed".to_string())),
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::NotConnected =>
+                {
+                    return None
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e.to_string())),
+            }
+        }
+    }
+}
+
+/// État des groupes de serveurs amont, partagé par tous les serveurs du Router:
+/// choix du serveur, requêtes en cours, échecs passifs et tests de santé.
+#[derive(Debug, Default)]
+pub struct UpstreamPools {
+    pools: HashMap<String, UpstreamPool>,
+    probes: HashMap<Token, HealthProbe>,
+    // Changements d'état des serveurs, écrits dans le journal d'erreurs
+    events: Vec<String>,
+}
+
+impl UpstreamPools {
+    pub fn new(groups: &HashMap<String, UpstreamGroup>) -> Self {
+        let now = Instant::now();
+        let pools = groups
+            .iter()
+            .map(|(name, group)| {
+                let peers = group
+                    .servers
+                    .iter()
+                    .map(|peer| PeerState {
+                        config: UpstreamPeer {
+                            weight: peer.weight.max(1),
+                            ..peer.clone()
+                        },
+                        target: UpstreamTarget::resolve(&upstream_address(&peer.address)),
+                        active: 0,
+                        fails: 0,
+                        fails_since: None,
+                        down_until: None,
+                        healthy: true,
+                        current: 0,
+                    })
+                    .collect();
+                let pool = UpstreamPool {
+                    strategy: group.strategy,
+                    peers,
+                    health_check: group.health_check.clone(),
+                    next_check: now,
+                };
+                (name.clone(), pool)
+            })
+            .collect();
+        Self {
+            pools,
+            ..Default::default()
+        }
+    }
+
+    /// Serveurs à essayer dans l'ordre pour une requête: celui choisi par la stratégie
+    /// du groupe, puis les autres serveurs disponibles. `None` si le groupe n'existe pas.
+    pub fn select(&mut self, group: &str, client_ip: &str) -> Option<Vec<(String, PeerId)>> {
+        let pool = self.pools.get_mut(group)?;
+        let now = Instant::now();
+        let available: Vec<usize> = (0..pool.peers.len())
+            .filter(|&i| pool.peers[i].is_available(now))
+            .collect();
+        if available.is_empty() {
+            return Some(vec![]);
+        }
+
+        let first = match pool.strategy {
+            BalanceStrategy::RoundRobin => pool.round_robin(&available),
+            BalanceStrategy::LeastConn => pool.least_conn(&available),
+            BalanceStrategy::IpHash => pool.ip_hash(&available, client_ip),
+        };
+        let order = std::iter::once(first).chain(available.into_iter().filter(|&i| i != first));
+        Some(
+            order
+                .map(|index| {
+                    let peer = PeerId {
+                        group: group.to_string(),
+                        index,
+                    };
+                    (pool.peers[index].config.address.clone(), peer)
+                })
+                .collect(),
+        )
+    }
+
+    /// Adresse résolue d'un serveur de groupe.
+    pub fn target(&self, peer: &PeerId) -> UpstreamTarget {
+        self.pools
+            .get(&peer.group)
+            .and_then(|pool| pool.peers.get(peer.index))
+            .map(|state| state.target.clone())
+            .unwrap_or_default()
+    }
+
+    /// Applique les comptes rendus des requêtes proxy: requêtes en cours et échecs
+    /// (max_fails échecs en fail_timeout secondes écartent le serveur pour fail_timeout).
+    pub fn report(&mut self, reports: Vec<PeerReport>) {
+        let now = Instant::now();
+        for report in reports {
+            let (peer, failed) = match report {
+                PeerReport::Connected(peer) => (peer, None),
+                PeerReport::Released { peer, failed } => (peer, Some(failed)),
+            };
+            let Some(state) = self
+                .pools
+                .get_mut(&peer.group)
+                .and_then(|pool| pool.peers.get_mut(peer.index))
+            else {
+                continue;
+            };
+            match failed {
+                None => state.active += 1,
+                Some(failed) => {
+                    state.active = state.active.saturating_sub(1);
+                    if !failed {
+                        state.fails = 0;
+                        continue;
+                    }
+                    let window = Duration::from_secs(state.config.fail_timeout);
+                    if state.fails_since.is_none_or(|since| now - since >= window) {
+                        state.fails = 0;
+                        state.fails_since = Some(now);
+                    }
+                    state.fails += 1;
+                    if state.config.max_fails > 0 && state.fails >= state.config.max_fails {
+                        state.fails = 0;
+                        state.fails_since = None;
+                        state.down_until = Some(now + window);
+                        self.events.push(format!(
+                            "upstream {} {}: marked down for {}s after {} failure(s)",
+                            peer.group,
+                            state.config.address,
+                            state.config.fail_timeout,
+                            state.config.max_fails
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Prochain test de santé à lancer ou à abandonner, `None` s'il n'y en a pas.
+    pub fn deadline(&self) -> Option<Instant> {
+        let checks = self
+            .pools
+            .values()
+            .filter(|pool| pool.health_check.is_some())
+            .map(|pool| pool.next_check);
+        let probes = self.probes.values().map(|probe| probe.deadline);
+        checks.chain(probes).min()
+    }
+
+    /// Abandonne les tests de santé trop lents et lance ceux arrivés à échéance,
+    /// avec les tokens fournis par `next_token`.
+    pub fn run_health_checks(
+        &mut self,
+        registry: &Registry,
+        mut next_token: impl FnMut() -> Token,
+    ) {
+        let now = Instant::now();
+        let expired: Vec<Token> = self
+            .probes
+            .iter()
+            .filter(|(_, probe)| probe.deadline <= now)
+            .map(|(token, _)| *token)
+            .collect();
+        for token in expired {
+            self.finish_probe(token, registry, Err("health check timed out".to_string()));
+        }
+
+        let mut failed = vec![];
+        for (name, pool) in &mut self.pools {
+            let Some(check) = &pool.health_check else {
+                continue;
+            };
+            if pool.next_check > now {
+                continue;
+            }
+            pool.next_check = now + Duration::from_secs(check.interval.max(1));
+            for (index, peer) in pool.peers.iter().enumerate() {
+                if self
+                    .probes
+                    .values()
+                    .any(|probe| probe.group == *name && probe.index == index)
+                {
+                    continue;
+                }
+                let address = &peer.config.address;
+                let token = next_token();
+                let socket = UpstreamSocket::connect(&peer.target).and_then(|mut socket| {
+                    socket.register(registry, token)?;
+                    Ok(socket)
+                });
+                match socket {
+                    Ok(socket) => {
+                        let request = format!(
+                            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+                            check.path,
+                            upstream_host(address)
+                        );
+                        let probe = HealthProbe {
+                            group: name.clone(),
+                            index,
+                            socket,
+                            outgoing: request.into_bytes(),
+                            written: 0,
+                            incoming: vec![],
+                            deadline: now + Duration::from_secs(check.timeout.max(1)),
+                        };
+                        self.probes.insert(token, probe);
+                    }
+                    Err(e) => failed.push((name.clone(), index, e.to_string())),
+                }
+            }
+        }
+        for (group, index, error) in failed {
+            self.set_health(&group, index, Err(error));
+        }
+    }
+
+    pub fn contains(&self, token: Token) -> bool {
+        self.probes.contains_key(&token)
+    }
+
+    /// Fait avancer le test de santé associé à `token`.
+    pub fn ready(&mut self, token: Token, registry: &Registry) {
+        let Some(probe) = self.probes.get_mut(&token) else {
+            return;
+        };
+        if let Some(result) = probe.advance() {
+            self.finish_probe(token, registry, result);
+        }
+    }
+
+    /// Écrit les changements d'état des serveurs dans le journal d'erreurs.
+    pub fn log_errors(&mut self, config: &Config) {
+        for event in self.events.drain(..) {
+            let e = io::Error::other(event);
+            Server::error_log(
+                &Request::default(),
+                config,
+                "upstream",
+                file!(),
+                line!(),
+                ServerError::IOError(&e),
+            );
+        }
+    }
+
+    fn finish_probe(&mut self, token: Token, registry: &Registry, result: Result<(), String>) {
+        if let Some(mut probe) = self.probes.remove(&token) {
+            let _ = probe.socket.deregister(registry);
+            self.set_health(&probe.group, probe.index, result);
+        }
+    }
+
+    fn set_health(&mut self, group: &str, index: usize, result: Result<(), String>) {
+        let Some(peer) = self
+            .pools
+            .get_mut(group)
+            .and_then(|pool| pool.peers.get_mut(index))
+        else {
+            return;
+        };
+        let healthy = result.is_ok();
+        if peer.healthy != healthy {
+            let state = match &result {
+                Ok(()) => "healthy again".to_string(),
+                Err(e) => format!("unhealthy ({})", e),
+            };
+            self.events.push(format!(
+                "upstream {} {}: {}",
+                group, peer.config.address, state
+            ));
+        }
+        peer.healthy = healthy;
+    }
+}
+// -------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pools(strategy: BalanceStrategy, weights: &[u32]) -> UpstreamPools {
+        let servers = weights
+            .iter()
+            .enumerate()
+            .map(|(i, weight)| UpstreamPeer {
+                address: format!("127.0.0.1:{}", 3000 + i),
+                weight: *weight,
+                max_fails: 2,
+                fail_timeout: 10,
+            })
+            .collect();
+        let group = UpstreamGroup {
+            strategy,
+            servers,
+            health_check: None,
+        };
+        UpstreamPools::new(&HashMap::from([("app".to_string(), group)]))
+    }
+
+    #[test]
+    fn test_upstream_target() {
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        assert_eq!(
+            UpstreamTarget::resolve("127.0.0.1:3000"),
+            UpstreamTarget::Tcp(addr)
+        );
+        assert_eq!(
+            UpstreamTarget::resolve("unix:/run/app.sock"),
+            UpstreamTarget::Unix(PathBuf::from("/run/app.sock"))
+        );
+        // Erreur de résolution renvoyée à la connexion, sans nouvelle résolution
+        let target = UpstreamTarget::resolve("no port");
+        assert!(matches!(target, UpstreamTarget::Unresolved(_)));
+        assert!(UpstreamSocket::connect(&target).is_err());
+
+        // Serveurs d'un groupe résolus à la création des pools
+        let mut pools = pools(BalanceStrategy::RoundRobin, &[1]);
+        let peer = pools.select("app", "").unwrap()[0].1.clone();
+        assert_eq!(pools.target(&peer), UpstreamTarget::Tcp(addr));
+    }
+
+    fn pick(pools: &mut UpstreamPools, client_ip: &str) -> usize {
+        pools.select("app", client_ip).unwrap()[0].1.index
+    }
+
+    #[test]
+    fn test_weighted_round_robin() {
+        let mut pools = pools(BalanceStrategy::RoundRobin, &[3, 1]);
+        let picks: Vec<usize> = (0..8).map(|_| pick(&mut pools, "")).collect();
+        assert_eq!(picks.iter().filter(|&&i| i == 0).count(), 6);
+        // Répartition lisse: le serveur léger n'attend pas 3 requêtes consécutives de trop
+        assert_eq!(&picks[..4], &[0, 0, 1, 0]);
+
+        let order = pools.select("app", "").unwrap();
+        assert_eq!(order.len(), 2);
+        assert!(pools.select("unknown", "").is_none());
+    }
+
+    #[test]
+    fn test_least_conn_and_ip_hash() {
+        let mut pools = pools(BalanceStrategy::LeastConn, &[1, 1]);
+        let first = pools.select("app", "").unwrap()[0].1.clone();
+        pools.report(vec![PeerReport::Connected(first.clone())]);
+        assert_ne!(pick(&mut pools, ""), first.index);
+        pools.report(vec![PeerReport::Released {
+            peer: first,
+            failed: false,
+        }]);
+
+        let mut pools = self::pools(BalanceStrategy::IpHash, &[1, 1, 1]);
+        let chosen = pick(&mut pools, "10.0.0.7");
+        assert!((0..5).all(|_| pick(&mut pools, "10.0.0.7") == chosen));
+    }
+
+    #[test]
+    fn test_passive_failures() {
+        let mut pools = pools(BalanceStrategy::RoundRobin, &[1, 1]);
+        let failed = |index| PeerReport::Released {
+            peer: PeerId {
+                group: "app".to_string(),
+                index,
+            },
+            failed: true,
+        };
+        pools.report(vec![failed(0)]);
+        assert_eq!(pools.select("app", "").unwrap().len(), 2);
+        // max_fails = 2: le serveur est écarté pendant fail_timeout
+        pools.report(vec![failed(0)]);
+        let order = pools.select("app", "").unwrap();
+        assert_eq!(order.len(), 1);
+        assert_eq!(order[0].1.index, 1);
+        assert_eq!(pools.events.len(), 1);
+    }
+}
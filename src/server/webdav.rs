@@ -0,0 +1,989 @@
+use chrono::{DateTime, Duration, Utc};
+use mio::net::TcpStream;
+use regex::{Regex, RegexSet};
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use uuid::Uuid;
+
+use super::{
+    path_size, sanitize_filename, RawResponse, Request, Response, Server, ServerError, ServerState,
+    Transfer, UploadUsage,
+};
+use crate::{
+    copy_recursive, get_mime_type, percent_decode, url_encode_path, CollisionPolicy, Config,
+};
+
+// -------------------------------------------------------------------------------------
+// WEBDAV
+// -------------------------------------------------------------------------------------
+const DAV_METHODS: &str =
+    "OPTIONS, GET, HEAD, POST, PUT, DELETE, PROPFIND, PROPPATCH, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+const LOCK_TIMEOUT: i64 = 60 * 60; // secondes
+const LOCK_TIMEOUT_MAX: i64 = 24 * 60 * 60;
+const XML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n";
+
+// Expressions compilées une seule fois pour toutes les requêtes
+static LOCK_TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(opaquelocktoken:[^>]+)>").unwrap());
+static LOCK_OWNER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<(?:[\w.-]+:)?owner\b[^>]*>(.*?)</(?:[\w.-]+:)?owner>").unwrap()
+});
+static XML_TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<(/?)(?:([A-Za-z_][\w.-]*):)?([A-Za-z_][\w.-]*)((?:\s[^>]*?)?)(/?)>").unwrap()
+});
+static XML_ANY_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+static XMLNS_PREFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"xmlns:([\w.-]+)\s*=\s*["']([^"']*)["']"#).unwrap());
+static XMLNS_DEFAULT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"xmlns\s*=\s*["']([^"']*)["']"#).unwrap());
+
+/// Verrou d'écriture WebDAV (classe 2).
+#[derive(Debug, Clone)]
+pub struct DavLock {
+    pub token: String,
+    pub path: String,
+    pub depth_infinity: bool,
+    pub exclusive: bool,
+    pub owner: String,
+    pub timeout: i64,
+    pub expires: DateTime<Utc>,
+}
+
+impl DavLock {
+    /// Le verrou s'applique-t-il à `path` ?
+    pub fn covers(&self, path: &str) -> bool {
+        self.path == path || (self.depth_infinity && is_descendant(path, &self.path))
+    }
+
+    fn to_xml(&self) -> String {
+        format!(
+            "<D:activelock><D:locktype><D:write/></D:locktype>\
+             <D:lockscope><D:{}/></D:lockscope>\
+             <D:depth>{}</D:depth>{}\
+             <D:timeout>Second-{}</D:timeout>\
+             <D:locktoken><D:href>{}</D:href></D:locktoken>\
+             <D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock>",
+            if self.exclusive {
+                "exclusive"
+            } else {
+                "shared"
+            },
+            if self.depth_infinity { "infinity" } else { "0" },
+            self.owner,
+            self.timeout,
+            self.token,
+            href(&self.path, false)
+        )
+    }
+}
+
+/// Verrous actifs, indexés par jeton.
+#[derive(Debug, Default)]
+pub struct LockStore {
+    pub locks: HashMap<String, DavLock>,
+}
+
+impl LockStore {
+    /// Retire les verrous expirés.
+    pub fn purge(&mut self) {
+        let now = Utc::now();
+        self.locks.retain(|_, lock| lock.expires > now);
+    }
+
+    /// Verrous qui portent sur `path`, ou sur ses descendants si `recursive`.
+    pub fn conflicts(&self, path: &str, recursive: bool) -> Vec<&DavLock> {
+        self.locks
+            .values()
+            .filter(|lock| lock.covers(path) || (recursive && is_descendant(&lock.path, path)))
+            .collect()
+    }
+
+    /// Vrai si tous les verrous concernés sont présentés dans l'en-tête If.
+    pub fn allows(&self, path: &str, recursive: bool, tokens: &[String]) -> bool {
+        self.conflicts(path, recursive)
+            .iter()
+            .all(|lock| tokens.contains(&lock.token))
+    }
+
+    /// Supprime les verrous de `path` et de ses descendants.
+    pub fn remove_under(&mut self, path: &str) {
+        self.locks
+            .retain(|_, lock| lock.path != path && !is_descendant(&lock.path, path));
+    }
+}
+
+/// Élément d'un corps XML WebDAV: (espace de noms, nom) et ceux de son parent.
+#[derive(Debug, Clone, PartialEq)]
+struct XmlElement {
+    ns: String,
+    name: String,
+    parent_ns: String,
+    parent_name: String,
+}
+
+impl XmlElement {
+    fn is(&self, ns: &str, name: &str) -> bool {
+        self.ns == ns && self.name == name
+    }
+
+    fn in_parent(&self, ns: &str, name: &str) -> bool {
+        self.parent_ns == ns && self.parent_name == name
+    }
+}
+
+impl Server {
+    pub fn is_webdav_method(method: &str) -> bool {
+        matches!(
+            method,
+            "HEAD"
+                | "PUT"
+                | "DELETE"
+                | "OPTIONS"
+                | "PROPFIND"
+                | "PROPPATCH"
+                | "MKCOL"
+                | "COPY"
+                | "MOVE"
+                | "LOCK"
+                | "UNLOCK"
+        )
+    }
+
+    /// Traite une requête WebDAV sur root_directory.
+    pub fn handle_webdav(
+        &self,
+        stream: &mut TcpStream,
+        request: &Request,
+        cookie: &String,
+        config: &Config,
+        state: &mut ServerState,
+    ) -> Option<Transfer> {
+        let ServerState {
+            locks,
+            upload_usage: usage,
+            ..
+        } = state;
+        // Verrous du dossier racine servi: indépendants d'un serveur à l'autre
+        let root = fs::canonicalize(&self.root_directory)
+            .unwrap_or_else(|_| PathBuf::from(&self.root_directory));
+        let locks = locks.entry(root).or_default();
+        locks.purge();
+        let url_path = dav_path(&request.path);
+        let tokens = Self::lock_tokens(request);
+
+        let response = match self.resolve_path("/", &url_path) {
+            None => RawResponse::status(403),
+            Some(path) => {
+                let result = match request.method.as_str() {
+                    "OPTIONS" => Ok(RawResponse::status(200)
+                        .header("DAV", "1, 2")
+                        .header("Allow", DAV_METHODS)
+                        .header("MS-Author-Via", "DAV")),
+                    "HEAD" => Ok(Self::dav_head(&path)),
+                    "PROPFIND" => self.dav_propfind(request, &url_path, &path, locks),
+                    "PROPPATCH" => Ok(Self::dav_proppatch(
+                        request, &url_path, &path, locks, &tokens,
+                    )),
+                    "MKCOL" => Self::dav_mkcol(request, &url_path, &path, locks, &tokens),
+                    "PUT" => self.dav_put(request, &url_path, &path, locks, &tokens, usage),
+                    "DELETE" => self.dav_delete(&url_path, &path, locks, &tokens, usage),
+                    "COPY" | "MOVE" => {
+                        self.dav_copy_move(request, &url_path, &path, locks, &tokens, usage)
+                    }
+                    "LOCK" => self.dav_lock(request, &url_path, &path, locks, &tokens),
+                    "UNLOCK" => Ok(Self::dav_unlock(request, &url_path, locks)),
+                    _ => Ok(RawResponse::status(405)),
+                };
+
+                match result {
+                    Ok(response) => response,
+                    Err(e) => {
+                        Self::error_log(
+                            request,
+                            config,
+                            "handle_webdav",
+                            file!(),
+                            line!(),
+                            ServerError::IOError(&e),
+                        );
+                        RawResponse::status(500)
+                    }
+                }
+            }
+        };
+
+        self.send_raw_response(stream, request, config, cookie, response)
+    }
+
+    /// Jetons de verrou présentés dans l'en-tête If.
+    fn lock_tokens(request: &Request) -> Vec<String> {
+        request
+            .header("If")
+            .map(|value| {
+                LOCK_TOKEN
+                    .captures_iter(value)
+                    .map(|c| c[1].to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn dav_head(path: &Path) -> RawResponse {
+        match fs::metadata(path) {
+            Err(_) => RawResponse::status(404),
+            Ok(metadata) if metadata.is_dir() => RawResponse::status(200)
+                .header("Content-Type", "text/html")
+                .header("Content-Length", "0"),
+            Ok(metadata) => RawResponse::status(200)
+                .header("Content-Type", get_mime_type(&path.to_string_lossy()))
+                .header("Content-Length", &metadata.len().to_string())
+                .header("Last-Modified", &http_date(&metadata))
+                .header("ETag", &etag(&metadata)),
+        }
+    }
+
+    fn dav_propfind(
+        &self,
+        request: &Request,
+        url_path: &str,
+        path: &Path,
+        locks: &LockStore,
+    ) -> std::io::Result<RawResponse> {
+        let Ok(metadata) = fs::metadata(path) else {
+            return Ok(RawResponse::status(404));
+        };
+
+        let depth = match request.header("Depth").map(|d| d.trim()) {
+            Some("0") => 0,
+            Some("1") => 1,
+            _ => {
+                return Ok(dav_xml(
+                    403,
+                    "<D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>".to_string(),
+                ))
+            }
+        };
+
+        // allprop (corps vide), propname ou liste de propriétés
+        let (names_only, requested) = if request.body.trim().is_empty() {
+            (false, None)
+        } else {
+            let Ok(elements) = parse_dav_xml(&request.body) else {
+                return Ok(RawResponse::status(400));
+            };
+            if !elements.first().is_some_and(|e| e.is("DAV:", "propfind")) {
+                return Ok(RawResponse::status(400));
+            }
+            let props: Vec<(String, String)> = elements
+                .iter()
+                .filter(|e| e.in_parent("DAV:", "prop"))
+                .map(|e| (e.ns.clone(), e.name.clone()))
+                .collect();
+            let names_only = elements.iter().any(|e| e.is("DAV:", "propname"));
+            let all = elements.iter().any(|e| e.is("DAV:", "allprop"));
+            (
+                names_only,
+                if all || names_only { None } else { Some(props) },
+            )
+        };
+
+        let mut resources = vec![(url_path.to_string(), path.to_path_buf(), metadata.clone())];
+        if depth == 1 && metadata.is_dir() {
+            let re = RegexSet::new(&self.exclusion).unwrap_or_else(|_| RegexSet::empty());
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                let Ok(child) = entry.metadata() else {
+                    continue;
+                };
+                if re.is_match(&name)
+                    || !(child.is_file() || (child.is_dir() && self.directory_listing))
+                {
+                    continue;
+                }
+                let child_url = match url_path {
+                    "/" => format!("/{}", name),
+                    _ => format!("{}/{}", url_path, name),
+                };
+                resources.push((child_url, entry.path(), child));
+            }
+        }
+
+        let mut body = String::from("<D:multistatus xmlns:D=\"DAV:\">");
+        for (url, path, metadata) in resources {
+            let live = self.live_properties(&url, &path, &metadata, locks);
+            let mut found = String::new();
+            let mut missing = String::new();
+
+            match &requested {
+                None => {
+                    for (name, value) in &live {
+                        match names_only {
+                            true => found += &format!("<D:{}/>", name),
+                            false => found += &format!("<D:{}>{}</D:{}>", name, value, name),
+                        }
+                    }
+                }
+                Some(props) => {
+                    for (ns, name) in props {
+                        match live.iter().find(|(n, _)| ns == "DAV:" && n == name) {
+                            Some((_, value)) => {
+                                found += &format!("<D:{}>{}</D:{}>", name, value, name)
+                            }
+                            None => missing += &empty_property(ns, name),
+                        }
+                    }
+                }
+            }
+
+            body += &format!(
+                "<D:response><D:href>{}</D:href>",
+                href(&url, metadata.is_dir())
+            );
+            if !found.is_empty() || requested.is_none() {
+                body += &propstat(&found, 200);
+            }
+            if !missing.is_empty() {
+                body += &propstat(&missing, 404);
+            }
+            body += "</D:response>";
+        }
+        body += "</D:multistatus>";
+
+        Ok(dav_xml(207, body))
+    }
+
+    /// Propriétés DAV: calculées depuis le système de fichiers.
+    fn live_properties(
+        &self,
+        url_path: &str,
+        path: &Path,
+        metadata: &Metadata,
+        locks: &LockStore,
+    ) -> Vec<(&'static str, String)> {
+        let created: DateTime<Utc> = metadata
+            .created()
+            .or_else(|_| metadata.modified())
+            .map_or(Utc::now(), DateTime::from);
+        let display_name = match url_path {
+            "/" => self.hostname.clone(),
+            _ => path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+        let discovery: String = locks
+            .locks
+            .values()
+            .filter(|lock| lock.covers(url_path))
+            .map(DavLock::to_xml)
+            .collect();
+
+        let mut props = vec![
+            ("creationdate", created.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+            ("displayname", xml_escape(&display_name)),
+            ("getlastmodified", http_date(metadata)),
+            (
+                "resourcetype",
+                match metadata.is_dir() {
+                    true => "<D:collection/>".to_string(),
+                    false => String::new(),
+                },
+            ),
+            (
+                "supportedlock",
+                "<D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>\
+                 <D:lockentry><D:lockscope><D:shared/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>"
+                    .to_string(),
+            ),
+            ("lockdiscovery", discovery),
+        ];
+        if metadata.is_file() {
+            props.push(("getcontentlength", metadata.len().to_string()));
+            props.push((
+                "getcontenttype",
+                get_mime_type(&path.to_string_lossy()).to_string(),
+            ));
+            props.push(("getetag", xml_escape(&etag(metadata))));
+        }
+        props
+    }
+
+    /// PROPPATCH minimal: les propriétés DAV: sont protégées, les autres sont acceptées
+    /// sans être conservées (pas de stockage de propriétés mortes).
+    fn dav_proppatch(
+        request: &Request,
+        url_path: &str,
+        path: &Path,
+        locks: &LockStore,
+        tokens: &[String],
+    ) -> RawResponse {
+        if !path.exists() {
+            return RawResponse::status(404);
+        }
+        if !locks.allows(url_path, false, tokens) {
+            return RawResponse::status(423);
+        }
+        let Ok(elements) = parse_dav_xml(&request.body) else {
+            return RawResponse::status(400);
+        };
+        if !elements
+            .first()
+            .is_some_and(|e| e.is("DAV:", "propertyupdate"))
+        {
+            return RawResponse::status(400);
+        }
+
+        let props: Vec<&XmlElement> = elements
+            .iter()
+            .filter(|e| e.in_parent("DAV:", "prop"))
+            .collect();
+        let protected = props.iter().any(|e| e.ns == "DAV:");
+
+        let mut body = format!(
+            "<D:multistatus xmlns:D=\"DAV:\"><D:response><D:href>{}</D:href>",
+            href(url_path, path.is_dir())
+        );
+        for prop in props {
+            let status = match (protected, prop.ns == "DAV:") {
+                (true, true) => 403,
+                (true, false) => 424,
+                _ => 200,
+            };
+            body += &propstat(&empty_property(&prop.ns, &prop.name), status);
+        }
+        body += "</D:response></D:multistatus>";
+        dav_xml(207, body)
+    }
+
+    fn dav_mkcol(
+        request: &Request,
+        url_path: &str,
+        path: &Path,
+        locks: &LockStore,
+        tokens: &[String],
+    ) -> std::io::Result<RawResponse> {
+        if !request.body_byte.is_empty() {
+            return Ok(RawResponse::status(415));
+        }
+        if path.exists() {
+            return Ok(RawResponse::status(405));
+        }
+        // Même nettoyage des noms que pour les envois
+        let name = url_path.rsplit('/').next().unwrap_or_default();
+        if sanitize_filename(name) != name {
+            return Ok(RawResponse::status(400));
+        }
+        if !path.parent().is_some_and(|p| p.is_dir()) {
+            return Ok(RawResponse::status(409));
+        }
+        if !locks.allows(url_path, false, tokens) {
+            return Ok(RawResponse::status(423));
+        }
+        fs::create_dir(path)?;
+        Ok(RawResponse::status(201))
+    }
+
+    fn dav_put(
+        &self,
+        request: &Request,
+        url_path: &str,
+        path: &Path,
+        locks: &LockStore,
+        tokens: &[String],
+        usage: &mut UploadUsage,
+    ) -> std::io::Result<RawResponse> {
+        if path.is_dir() {
+            return Ok(RawResponse::status(405));
+        }
+        if !locks.allows(url_path, false, tokens) {
+            return Ok(RawResponse::status(423));
+        }
+
+        // Règles des envois: nom, extension et scripts. PUT remplace toujours la ressource,
+        // quelle que soit la politique de collision du dossier
+        let filename = url_path.rsplit('/').next().unwrap_or_default();
+        if sanitize_filename(filename) != filename {
+            return Ok(RawResponse::status(400));
+        }
+        if let Err((status, _)) = self.check_upload_path(url_path) {
+            return Ok(RawResponse::status(status));
+        }
+        if !path.parent().is_some_and(|p| p.is_dir()) {
+            return Ok(RawResponse::status(409));
+        }
+        let body = request.raw_body();
+        if let Err((status, _)) =
+            self.check_upload_quota(&request.id_session, body.len() as u64, usage)
+        {
+            return Ok(RawResponse::status(status));
+        }
+
+        let existed = path.exists();
+        let replaced = path_size(path);
+        fs::write(path, body)?;
+        self.record_upload(&request.id_session, body.len() as u64, replaced, usage);
+        Ok(RawResponse::status(if existed { 204 } else { 201 }))
+    }
+
+    fn dav_delete(
+        &self,
+        url_path: &str,
+        path: &Path,
+        locks: &mut LockStore,
+        tokens: &[String],
+        usage: &mut UploadUsage,
+    ) -> std::io::Result<RawResponse> {
+        if url_path == "/" {
+            return Ok(RawResponse::status(403));
+        }
+        if !path.exists() {
+            return Ok(RawResponse::status(404));
+        }
+        if !locks.allows(url_path, true, tokens) {
+            return Ok(RawResponse::status(423));
+        }
+
+        let size = path_size(path);
+        match path.is_dir() {
+            true => fs::remove_dir_all(path)?,
+            false => fs::remove_file(path)?,
+        }
+        usage.update_disk(&self.root_directory, 0, size);
+        locks.remove_under(url_path);
+        Ok(RawResponse::status(204))
+    }
+
+    fn dav_copy_move(
+        &self,
+        request: &Request,
+        url_path: &str,
+        path: &Path,
+        locks: &mut LockStore,
+        tokens: &[String],
+        usage: &mut UploadUsage,
+    ) -> std::io::Result<RawResponse> {
+        let is_move = request.method == "MOVE";
+        let Some(destination) = request.header("Destination") else {
+            return Ok(RawResponse::status(400));
+        };
+
+        // "http://hote:port/chemin" -> "/chemin"
+        let dest_url = match destination.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+            None => destination.as_str(),
+        };
+        let dest_url = dav_path(&percent_decode(
+            dest_url.split('?').next().unwrap_or_default(),
+        ));
+        let Some(target) = self.resolve_path("/", &dest_url) else {
+            return Ok(RawResponse::status(403));
+        };
+
+        if !path.exists() {
+            return Ok(RawResponse::status(404));
+        }
+        if dest_url == url_path || url_path == "/" || is_descendant(&dest_url, url_path) {
+            return Ok(RawResponse::status(403));
+        }
+        if !target.parent().is_some_and(|p| p.is_dir()) {
+            return Ok(RawResponse::status(409));
+        }
+        if (is_move && !locks.allows(url_path, true, tokens))
+            || !locks.allows(&dest_url, true, tokens)
+        {
+            return Ok(RawResponse::status(423));
+        }
+
+        // Règles des envois appliquées à la destination: nom, extensions, scripts et quotas
+        let (dest_dir, dest_name) = dest_url.rsplit_once('/').unwrap_or_default();
+        if sanitize_filename(dest_name) != dest_name {
+            return Ok(RawResponse::status(400));
+        }
+        if let Err((status, _)) = self.check_upload_tree(path, &dest_url) {
+            return Ok(RawResponse::status(status));
+        }
+        let size = if is_move { 0 } else { path_size(path) };
+        if let Err((status, _)) = self.check_upload_quota(&request.id_session, size, usage) {
+            return Ok(RawResponse::status(status));
+        }
+
+        // Le client a choisi le nom de destination: seule la règle Overwrite permet d'écraser
+        let overwrite = request.header("Overwrite").is_none_or(|o| o.trim() != "F")
+            && self.upload_policy(dest_dir).collision == CollisionPolicy::Overwrite;
+        let existed = target.exists();
+        let replaced = path_size(&target);
+        if existed {
+            if !overwrite {
+                return Ok(RawResponse::status(412));
+            }
+            match target.is_dir() {
+                true => fs::remove_dir_all(&target)?,
+                false => fs::remove_file(&target)?,
+            }
+            locks.remove_under(&dest_url);
+        }
+
+        if is_move {
+            // rename échoue entre deux systèmes de fichiers: copie puis suppression
+            if fs::rename(path, &target).is_err() {
+                copy_recursive(path, &target)?;
+                match path.is_dir() {
+                    true => fs::remove_dir_all(path)?,
+                    false => fs::remove_file(path)?,
+                }
+            }
+            locks.remove_under(url_path);
+        } else if path.is_dir() && request.header("Depth").is_some_and(|d| d.trim() == "0") {
+            fs::create_dir(&target)?;
+        } else {
+            copy_recursive(path, &target)?;
+        }
+        self.record_upload(&request.id_session, size, replaced, usage);
+
+        Ok(RawResponse::status(if existed { 204 } else { 201 }))
+    }
+
+    fn dav_lock(
+        &self,
+        request: &Request,
+        url_path: &str,
+        path: &Path,
+        locks: &mut LockStore,
+        tokens: &[String],
+    ) -> std::io::Result<RawResponse> {
+        let timeout = request
+            .header("Timeout")
+            .and_then(|t| {
+                t.split(',')
+                    .find_map(|v| v.trim().strip_prefix("Second-")?.parse::<i64>().ok())
+            })
+            .unwrap_or(LOCK_TIMEOUT)
+            .min(LOCK_TIMEOUT_MAX);
+
+        // Sans corps: rafraîchissement d'un verrou existant
+        if request.body.trim().is_empty() {
+            let lock = locks
+                .locks
+                .values_mut()
+                .find(|lock| tokens.contains(&lock.token) && lock.covers(url_path));
+            return Ok(match lock {
+                Some(lock) => {
+                    lock.timeout = timeout;
+                    lock.expires = Utc::now() + Duration::seconds(timeout);
+                    dav_xml(200, lock_discovery(lock))
+                }
+                None => RawResponse::status(412),
+            });
+        }
+
+        let Ok(elements) = parse_dav_xml(&request.body) else {
+            return Ok(RawResponse::status(400));
+        };
+        if !elements.first().is_some_and(|e| e.is("DAV:", "lockinfo")) {
+            return Ok(RawResponse::status(400));
+        }
+        let exclusive = !elements
+            .iter()
+            .any(|e| e.in_parent("DAV:", "lockscope") && e.name == "shared");
+        let depth_infinity = request.header("Depth").is_none_or(|d| d.trim() != "0");
+
+        let conflicts = locks.conflicts(url_path, depth_infinity);
+        if conflicts.iter().any(|lock| exclusive || lock.exclusive) {
+            return Ok(RawResponse::status(423));
+        }
+
+        // Verrouiller une ressource absente crée un fichier vide
+        let mut status = 200;
+        if !path.exists() {
+            if !path.parent().is_some_and(|p| p.is_dir()) {
+                return Ok(RawResponse::status(409));
+            }
+            if let Err((status, _)) = self.check_upload_path(url_path) {
+                return Ok(RawResponse::status(status));
+            }
+            fs::write(path, b"")?;
+            status = 201;
+        }
+
+        let lock = DavLock {
+            token: format!("opaquelocktoken:{}", Uuid::new_v4()),
+            path: url_path.to_string(),
+            depth_infinity,
+            exclusive,
+            owner: lock_owner(&request.body),
+            timeout,
+            expires: Utc::now() + Duration::seconds(timeout),
+        };
+        let response = dav_xml(status, lock_discovery(&lock))
+            .header("Lock-Token", &format!("<{}>", lock.token));
+        locks.locks.insert(lock.token.clone(), lock);
+        Ok(response)
+    }
+
+    fn dav_unlock(request: &Request, url_path: &str, locks: &mut LockStore) -> RawResponse {
+        let token = request
+            .header("Lock-Token")
+            .map(|t| {
+                t.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        match locks.locks.get(&token) {
+            Some(lock) if lock.covers(url_path) => {
+                locks.locks.remove(&token);
+                RawResponse::status(204)
+            }
+            _ => dav_xml(
+                409,
+                "<D:error xmlns:D=\"DAV:\"><D:lock-token-matches-request-uri/></D:error>"
+                    .to_string(),
+            ),
+        }
+    }
+}
+// -------------------------------------------------------------------------------------
+
+/// Normalise un chemin d'URL: "/a/./b/../c/" -> "/a/c".
+pub fn dav_path(path: &str) -> String {
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+fn is_descendant(path: &str, ancestor: &str) -> bool {
+    match ancestor {
+        "/" => path != "/",
+        _ => path.starts_with(&format!("{}/", ancestor)),
+    }
+}
+
+fn href(url_path: &str, is_directory: bool) -> String {
+    let mut href = url_encode_path(url_path);
+    if is_directory && !href.ends_with('/') {
+        href.push('/');
+    }
+    href
+}
+
+fn dav_xml(status: u16, body: String) -> RawResponse {
+    RawResponse::status(status).body(
+        "application/xml; charset=\"utf-8\"",
+        (XML_HEADER.to_string() + &body).into_bytes(),
+    )
+}
+
+fn propstat(props: &str, status: u16) -> String {
+    format!(
+        "<D:propstat><D:prop>{}</D:prop><D:status>HTTP/1.1 {} {}</D:status></D:propstat>",
+        props,
+        status,
+        Response::status_text(status)
+    )
+}
+
+fn empty_property(ns: &str, name: &str) -> String {
+    match ns {
+        "DAV:" => format!("<D:{}/>", name),
+        _ => format!("<{} xmlns=\"{}\"/>", name, xml_escape(ns)),
+    }
+}
+
+fn lock_discovery(lock: &DavLock) -> String {
+    format!(
+        "<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery>{}</D:lockdiscovery></D:prop>",
+        lock.to_xml()
+    )
+}
+
+/// Propriétaire déclaré dans le corps du LOCK, réduit à son texte.
+fn lock_owner(body: &str) -> String {
+    let Some(caps) = LOCK_OWNER.captures(body) else {
+        return String::new();
+    };
+    let inner = &caps[1];
+    let text = XML_ANY_TAG.replace_all(inner, "");
+    let text = xml_escape(text.trim());
+    match inner.contains("href") {
+        true => format!("<D:owner><D:href>{}</D:href></D:owner>", text),
+        false => format!("<D:owner>{}</D:owner>", text),
+    }
+}
+
+fn http_date(metadata: &Metadata) -> String {
+    let modified: DateTime<Utc> = metadata.modified().map_or(Utc::now(), DateTime::from);
+    modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn etag(metadata: &Metadata) -> String {
+    let modified: DateTime<Utc> = metadata.modified().map_or(Utc::now(), DateTime::from);
+    format!("\"{:x}-{:x}\"", metadata.len(), modified.timestamp())
+}
+
+pub fn xml_escape(str: &str) -> String {
+    str.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Lit un corps XML WebDAV et renvoie ses éléments dans l'ordre du document.
+/// Échoue si les balises ne sont pas équilibrées ou si un préfixe est inconnu.
+fn parse_dav_xml(body: &str) -> Result<Vec<XmlElement>, ()> {
+    // Espaces de noms par préfixe, de portée globale pour rester simple
+    let prefixes: HashMap<String, String> = XMLNS_PREFIX
+        .captures_iter(body)
+        .map(|c| (c[1].to_string(), c[2].to_string()))
+        .collect();
+
+    // (nom qualifié, espace de noms par défaut, espace de noms, nom local)
+    let mut stack: Vec<(String, String, String, String)> = vec![];
+    let mut elements = vec![];
+
+    for caps in XML_TAG.captures_iter(body) {
+        let prefix = caps.get(2).map(|m| m.as_str());
+        let name = caps[3].to_string();
+        let qualified = format!("{}:{}", prefix.unwrap_or_default(), name);
+
+        if &caps[1] == "/" {
+            match stack.pop() {
+                Some((open, ..)) if open == qualified => continue,
+                _ => return Err(()),
+            }
+        }
+
+        let attributes = &caps[4];
+        let default_ns = match XMLNS_DEFAULT.captures(attributes) {
+            Some(c) => c[1].to_string(),
+            None => stack.last().map(|s| s.1.clone()).unwrap_or_default(),
+        };
+        let ns = match prefix {
+            Some(p) => prefixes.get(p).cloned().ok_or(())?,
+            None => default_ns.clone(),
+        };
+        let (parent_ns, parent_name) = stack
+            .last()
+            .map(|s| (s.2.clone(), s.3.clone()))
+            .unwrap_or_default();
+
+        elements.push(XmlElement {
+            ns: ns.clone(),
+            name: name.clone(),
+            parent_ns,
+            parent_name,
+        });
+        if &caps[5] != "/" {
+            stack.push((qualified, default_ns, ns, name));
+        }
+    }
+
+    match stack.is_empty() && !elements.is_empty() {
+        true => Ok(elements),
+        false => Err(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UploadPolicy;
+
+    #[test]
+    fn test_parse_dav_xml() {
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+            <D:propfind xmlns:D="DAV:"><D:prop>
+                <D:getcontentlength/><foo xmlns="http://example.com/"/>
+            </D:prop></D:propfind>"#;
+        let elements = parse_dav_xml(body).unwrap();
+        assert!(elements[0].is("DAV:", "propfind"));
+        let props: Vec<(&str, &str)> = elements
+            .iter()
+            .filter(|e| e.in_parent("DAV:", "prop"))
+            .map(|e| (e.ns.as_str(), e.name.as_str()))
+            .collect();
+        assert_eq!(
+            props,
+            vec![("DAV:", "getcontentlength"), ("http://example.com/", "foo")]
+        );
+
+        assert!(parse_dav_xml("<D:propfind xmlns:D=\"DAV:\"><D:prop></D:propfind>").is_err());
+    }
+
+    #[test]
+    fn test_dav_put_replaces() {
+        let dir = std::env::temp_dir().join(format!("dav_put_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut server = Server::new(
+            "127.0.0.1".to_string(),
+            "localhost".to_string(),
+            vec![8080],
+            dir.to_string_lossy().to_string(),
+            String::new(),
+            String::new(),
+            0,
+            vec![],
+            false,
+            vec![],
+            vec![],
+        );
+        server.upload_policies = vec![UploadPolicy {
+            location: "/".to_string(),
+            collision: CollisionPolicy::Rename,
+            ..Default::default()
+        }];
+        let locks = LockStore::default();
+        let mut usage = UploadUsage::default();
+        let put = |body: &[u8], usage: &mut UploadUsage| {
+            let mut request = Request::default();
+            request.method = "PUT".to_string();
+            request.body_byte = body.to_vec();
+            let path = dir.join("notes.txt");
+            server
+                .dav_put(&request, "/notes.txt", &path, &locks, &[], usage)
+                .unwrap()
+                .status
+        };
+
+        // Le second PUT remplace le fichier malgré la politique "rename"
+        assert_eq!(put(b"first", &mut usage), 201);
+        assert_eq!(put(b"second", &mut usage), 204);
+        assert_eq!(fs::read(dir.join("notes.txt")).unwrap(), b"second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_lock_coverage() {
+        let mut store = LockStore::default();
+        store.locks.insert(
+            "opaquelocktoken:1".to_string(),
+            DavLock {
+                token: "opaquelocktoken:1".to_string(),
+                path: "/d".to_string(),
+                depth_infinity: true,
+                exclusive: true,
+                owner: String::new(),
+                timeout: 60,
+                expires: Utc::now() + Duration::seconds(60),
+            },
+        );
+        assert!(!store.allows("/d/g/file.png", false, &[]));
+        assert!(store.allows("/d/g/file.png", false, &["opaquelocktoken:1".to_string()]));
+        assert!(store.allows("/other", false, &[]));
+        assert!(!store.allows("/", true, &[]));
+        assert_eq!(dav_path("/a/./b/../c/"), "/a/c");
+    }
+}
@@ -0,0 +1,7 @@
+import html
+import os
+
+print("Content-Type: text/html; charset=utf-8")
+print()
+print("<h1>Hello from Python</h1>")
+print("<p>Query: {}</p>".format(html.escape(os.environ.get("QUERY_STRING", ""))))
@@ -1,3 +1,5 @@
+print "Content-Type: text/plain; charset=utf-8\r\n\r\n"
+
 class Personne
   def initialize(nom, age)
     @nom = nom
@@ -1,3 +1,5 @@
+print "Content-Type: text/plain; charset=utf-8\r\n\r\n"
+
 class Animal
   def parler
     puts "Grrr!"
@@ -1 +1,3 @@
+print "Content-Type: text/plain; charset=utf-8\r\n\r\n"
+
 puts "Hello World"